@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+/// Path from the controller output to the plant: a transport delay, then a
+/// dead-band that drops small commands, then a slew-rate limit. The delay is
+/// resolved to the integration step it is applied at.
+#[derive(Clone, PartialEq)]
+pub struct Actuator {
+    /// Dead time (s)
+    pub delay: f64,
+    /// Largest rate of change of the output, per second
+    pub slew_rate: f64,
+    /// Commands smaller than this in magnitude give no output
+    pub dead_band: f64,
+    pub output: f64,
+    history: VecDeque<(f64, f64)>,
+}
+
+impl Default for Actuator {
+    fn default() -> Self {
+        Actuator {
+            delay: 0.,
+            slew_rate: f64::INFINITY,
+            dead_band: 0.,
+            output: 0.,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl Actuator {
+    /// Feeds the command issued at time `t` and returns the output held for the next `dt`
+    pub fn apply(&mut self, t: f64, command: f64, dt: f64) -> f64 {
+        self.history.push_back((t, command));
+        let due = t - self.delay + 1e-12;
+        while self.history.len() > 1 && self.history[1].0 <= due {
+            self.history.pop_front();
+        }
+        let delayed = match self.history.front() {
+            Some(&(issued, command)) if issued <= due => command,
+            _ => 0.,
+        };
+        let target = if delayed.abs() < self.dead_band {
+            0.
+        } else {
+            delayed
+        };
+        let step = self.slew_rate * dt;
+        if (target - self.output).abs() <= step {
+            self.output = target;
+        } else {
+            self.output += step * (target - self.output).signum();
+        }
+        self.output
+    }
+
+    pub fn reset(&mut self) {
+        self.output = 0.;
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_drops_and_ramps_the_command() {
+        let mut actuator = Actuator {
+            delay: 0.05,
+            ..Default::default()
+        };
+        let out: Vec<_> = (0..10)
+            .map(|i| actuator.apply(i as f64 * 0.01, 1., 0.01))
+            .collect();
+        assert_eq!(out[4], 0.);
+        assert_eq!(out[5], 1.);
+
+        let mut actuator = Actuator {
+            dead_band: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(actuator.apply(0., 0.4, 0.01), 0.);
+        assert_eq!(actuator.apply(0.01, 0.6, 0.01), 0.6);
+
+        let mut actuator = Actuator {
+            slew_rate: 10.,
+            ..Default::default()
+        };
+        for i in 0..5 {
+            actuator.apply(i as f64 * 0.01, 1., 0.01);
+        }
+        assert!((actuator.output - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn dead_time_replays_the_command_until_reset() {
+        let mut actuator = Actuator {
+            delay: 0.025,
+            ..Default::default()
+        };
+        // a delay between ticks resolves to the tick after it
+        for i in 0..20 {
+            let out = actuator.apply(i as f64 * 0.01, i as f64, 0.01);
+            assert_eq!(out, i.max(3) as f64 - 3., "tick {i}");
+        }
+        actuator.reset();
+        assert_eq!(actuator.apply(0.2, 1., 0.01), 0.);
+    }
+}
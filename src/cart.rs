@@ -2,245 +2,1184 @@
 
 use std::f64::consts::PI;
 
-use macroquad::prelude::*;
+use crate::{
+    actuator::Actuator,
+    controller::{Controller, ControllerKind, Pid, PositionLoop},
+    disturbance::Disturbances,
+    friction::Friction,
+    hand::Hand,
+    integrator::{DormandPrince, IntegratorKind},
+    linalg::{inverse, mul_vec},
+    linalg::{zeros, Matrix},
+    lqr::Lqr,
+    motor::Motor,
+    observer::{Estimator, Observer},
+    params::{CartParams, Plant},
+    sensor::Sensors,
+    state::{wrap_angle, State},
+    swingup::SwingUp,
+    track::Track,
+};
 
-use crate::{camera::CameraDynamics, state::State};
-#[derive(PartialEq, Eq)]
-pub enum Integrator {
-    Euler,
-    RungeKutta4,
-}
+/// Longest frame the simulation catches up on, so a stall doesn't snowball
+const MAX_FRAME_TIME: f64 = 0.25;
 
-impl Default for Integrator {
-    fn default() -> Self {
-        Self::RungeKutta4
-    }
-}
-
-#[derive(PartialEq)]
 pub struct Cart {
     pub F: f64,
     pub Fclamp: f64,
     pub Finp: f64,
-    pub ui_scale: f32,
+    /// Manual push direction set by the front-end, scaled by `Finp`
+    pub push: f64,
     pub enable: bool,
-    pub pid: (f64, f64, f64),
-    pub error: f64,
-    pub int: f64,
+    pub controller: ControllerKind,
+    pub pid: Pid,
+    pub lqr: Lqr,
+    pub swing_up: SwingUp,
+    /// User supplied law, selected with `ControllerKind::Custom`
+    pub custom: Option<Box<dyn Controller>>,
+    pub setpoint: f64,
+    pub position: PositionLoop,
+    /// Run the controller at `sample_rate` with its output held in between,
+    /// instead of on every integration step
+    pub discrete: bool,
+    pub sample_rate: f64,
+    /// Holds the simulation still, `step_once` advancing it a tick at a time
+    pub paused: bool,
+    /// Simulated seconds per real second
+    pub time_scale: f64,
+    /// Latest controller output, before clamping
+    pub u: f64,
+    next_sample: f64,
+    pub time: f64,
+    accumulator: f64,
     pub state: State,
-    pub integrator: Integrator,
+    /// Total energy when the drift measurement was last zeroed
+    pub energy_reference: f64,
+    /// State at the start of the last tick
+    pub previous: State,
+    /// The velocity-based schemes step `(x, th)` with `(v, w)`; since the kinetic
+    /// energy couples `v` and `w` through `cos th` they are only nearly symplectic here
+    pub integrator: IntegratorKind,
+    pub adaptive: DormandPrince,
     pub steps: i32,
-    pub m: f64,
-    pub M: f64,
-    pub mw: f64,
-    pub ml: f64,
-    pub l: f64,
-    pub b1: f64,
-    pub b2: f64,
-    pub R: f64,
-    pub camera: CameraDynamics,
-    g: f64,
-    m1: f64,
-    m2: f64,
-    m3: f64,
+    pub plant: Plant,
+    pub params: CartParams,
+    pub friction: Friction,
+    pub track: Track,
+    pub motor: Motor,
+    pub actuator: Actuator,
+    pub sensors: Sensors,
+    /// What the controller last saw of the state
+    pub measured: State,
+    pub observer: Observer,
+    /// Voltage across the motor terminals
+    pub V: f64,
+    pub disturbances: Disturbances,
+    /// Scheduled force on the cart and torque on the first link over the last interval
+    pub load: [f64; 2],
+    /// The user's grip on the first link
+    pub hand: Hand,
 }
 
 impl Default for Cart {
     fn default() -> Self {
-        let (M, m, ml, mw) = (5., 0.5, 1., 1.);
-        let m1 = m + M + ml + 3. * mw;
-        let m2 = m + ml / 3.;
-        let m3 = m + ml / 2.;
-
-        Cart {
-            m,
-            M,
-            l: 1.,
-            g: 9.80665,
+        let mut cart = Cart {
             F: 0.,
             Fclamp: 400.,
             Finp: 20.,
-            int: 0.,
-            error: 0.,
-            R: 0.1,
             state: State::default(),
-            b1: 0.01,
-            b2: 0.005,
-            ui_scale: 0.3,
-            mw,
-            ml,
-            m1,
-            m2,
-            m3,
-            pid: (40., 8., 2.5),
+            push: 0.,
+            controller: ControllerKind::default(),
+            pid: Pid::default(),
+            lqr: Lqr::default(),
+            swing_up: SwingUp::default(),
+            custom: None,
+            setpoint: PI,
+            position: PositionLoop::default(),
+            discrete: false,
+            sample_rate: 200.,
+            paused: false,
+            time_scale: 1.,
+            u: 0.,
+            next_sample: 0.,
+            time: 0.,
+            accumulator: 0.,
+            previous: State::default(),
             steps: 5,
             enable: true,
-            integrator: Integrator::default(),
-            camera: CameraDynamics::default(),
-        }
+            integrator: IntegratorKind::default(),
+            adaptive: DormandPrince::default(),
+            plant: Plant::default(),
+            params: CartParams::default(),
+            friction: Friction::default(),
+            track: Track::default(),
+            motor: Motor::default(),
+            actuator: Actuator::default(),
+            sensors: Sensors::default(),
+            measured: State::default(),
+            observer: Observer::default(),
+            V: 0.,
+            disturbances: Disturbances::default(),
+            load: [0.; 2],
+            hand: Hand::default(),
+            energy_reference: 0.,
+        };
+        cart.reset_energy_drift();
+        cart
     }
 }
 
 impl Cart {
+    /// Advances the simulation by a frame of `dt` real seconds, sped up by
+    /// `time_scale`, in fixed ticks of `timestep()`, carrying the remainder
+    /// over to the next frame. Nothing moves while paused.
     pub fn update(&mut self, dt: f64) {
-        self.camera.update(self.state.x, self.state.v, dt);
-        let steps = if dt > 0.02 {
-            ((self.steps * 60) as f64 * dt) as i32
+        if self.paused {
+            return;
+        }
+        let h = self.timestep();
+        // the cap grows with the speed-up so fast-forward isn't throttled by it
+        let cap = MAX_FRAME_TIME * self.time_scale.max(1.);
+        self.accumulator = (self.accumulator + dt * self.time_scale).min(cap);
+        // the tolerance keeps float round-off from dropping a tick
+        while self.accumulator >= h * (1. - 1e-9) {
+            self.previous = self.state;
+            self.step();
+            self.accumulator -= h;
+        }
+    }
+
+    /// Advances exactly one tick and draws it without interpolation, for
+    /// stepping through a transient while paused
+    pub fn step_once(&mut self) {
+        self.step();
+        self.previous = self.state;
+        self.accumulator = 0.;
+    }
+
+    /// Length of one physics tick, `steps` ticks per 1/60 s
+    pub fn timestep(&self) -> f64 {
+        1. / (60. * self.steps as f64)
+    }
+
+    /// Fraction of a tick left in the accumulator, for interpolating drawn frames
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.timestep()).clamp(0., 1.)
+    }
+
+    /// State blended between the last two ticks to draw between them
+    pub fn interpolated(&self) -> State {
+        let (a, b, t) = (&self.previous, &self.state, self.alpha());
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        State::from(
+            lerp(a.x, b.x),
+            lerp(a.v, b.v),
+            lerp(a.w, b.w),
+            a.th + wrap_angle(b.th - a.th) * t,
+        )
+        .with_second_link(a.th2 + wrap_angle(b.th2 - a.th2) * t, lerp(a.w2, b.w2))
+        .with_current(lerp(a.i, b.i))
+    }
+
+    /// Advances the simulation by exactly one tick of `timestep()`
+    pub fn step(&mut self) {
+        if self.balancer() == ControllerKind::Lqr {
+            match self.plant {
+                Plant::Single | Plant::Furuta => {
+                    let (a, b) = self.linearize::<4>();
+                    self.lqr.update_gain::<4, 8>(a, b);
+                }
+                Plant::Double => {
+                    let (a, b) = self.linearize::<6>();
+                    self.lqr.update_gain::<6, 12>(a, b);
+                }
+            }
+        }
+        if self.observer.source == Estimator::Luenberger {
+            if self.plant == Plant::Double {
+                self.observer.update_gain::<6, 3, 12>(self.linearize().0);
+            } else {
+                self.observer.update_gain::<4, 2, 8>(self.linearize().0);
+            }
+        }
+        self.pid.limit = if self.motor.enable {
+            self.motor.v_max
         } else {
-            self.steps
-        };
-        let dt = dt / steps as f64;
-        for _ in 0..steps {
-            self.error = PI - self.state.th;
-            self.int += self.error * dt;
-            self.F = 0.;
-            if self.enable {
-                self.F = (10.
-                    * (self.error * self.pid.0 + self.int * self.pid.1
-                        - self.state.w * self.pid.2))
-                    .clamp(-self.Fclamp, self.Fclamp);
-            }
-            if is_key_down(KeyCode::Left) {
-                self.F = -self.Finp;
-                self.int = 0.
-            } else if is_key_down(KeyCode::Right) {
-                self.F = self.Finp;
-                self.int = 0.
-            }
-            let k1 = self.process_state(self.state);
-            if self.integrator == Integrator::Euler {
-                self.state.update(k1, dt);
-                continue;
-            }
-            let k2 = self.process_state(self.state.after(k1, dt * 0.5));
-            let k3 = self.process_state(self.state.after(k2, dt * 0.5));
-            let k4 = self.process_state(self.state.after(k3, dt));
-
-            let k_avg = (
-                (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0) / 6.0,
-                (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1) / 6.0,
-                (k1.2 + 2.0 * k2.2 + 2.0 * k3.2 + k4.2) / 6.0,
-                (k1.3 + 2.0 * k2.3 + 2.0 * k3.3 + k4.3) / 6.0,
-            );
-            self.state.update(k_avg, dt);
+            self.Fclamp
+        };
+        // LQR regulates the position itself, the other laws get a tilted setpoint
+        self.lqr.x_ref = if self.position.enable {
+            self.position.target
+        } else {
+            0.
+        };
+        let mut remaining = self.timestep();
+        while remaining > 0. {
+            let mut h = remaining;
+            if self.discrete {
+                let period = 1. / self.sample_rate;
+                // a faster rate takes effect now rather than after the old period
+                self.next_sample = self.next_sample.min(self.time + period);
+                if self.next_sample <= self.time + 1e-12 {
+                    self.sample_controller(period);
+                    self.next_sample += period;
+                    if self.next_sample <= self.time {
+                        self.next_sample = self.time + period;
+                    }
+                }
+                h = h.min(self.next_sample - self.time);
+            } else {
+                self.sample_controller(h);
+            }
+            let command = if self.push != 0. || !self.enable {
+                0.
+            } else {
+                self.u
+            };
+            let command = self.actuator.apply(self.time, command, h);
+            if self.motor.enable {
+                // the push acts alongside the motor, whose terminals are shorted meanwhile
+                self.V = command.clamp(-self.motor.v_max, self.motor.v_max);
+                self.F = self.push * self.Finp;
+            } else {
+                self.V = 0.;
+                self.F = if self.push != 0. {
+                    self.push * self.Finp
+                } else {
+                    command.clamp(-self.Fclamp, self.Fclamp)
+                };
+            }
+            self.load = self.disturbances.loads(self.time, h);
+            self.integrate(h);
+            self.collide();
+            self.time += h;
+            remaining -= h;
         }
     }
 
-    pub fn process_state(&self, state: State) -> (f64, f64, f64, f64) {
-        let (_, v, w, th) = state.unpack();
+    /// Evaluates the controller, whose output is then held until the next sample
+    fn sample_controller(&mut self, dt: f64) {
+        self.measured = if self.sensors.enable {
+            let r = self.params.drive_radius(self.plant);
+            self.sensors.measure(&self.state, r, dt)
+        } else {
+            self.state
+        };
+        let variances = if self.sensors.enable {
+            self.sensors.variances(self.params.drive_radius(self.plant))
+        } else {
+            [0.; 3]
+        };
+        let mut observer = std::mem::take(&mut self.observer);
+        let force = self.force();
+        let state = observer.update(&self.measured, dt, variances, |y| {
+            self.derivatives(y, [force, 0.])
+        });
+        self.observer = observer;
+        let t = self.time;
+        if self.controller == ControllerKind::SwingUp
+            && self
+                .swing_up
+                .update_mode(&state, self.setpoint, self.plant == Plant::Double)
+        {
+            self.position.reset();
+            self.active_controller().reset();
+        }
+        let cascade = self.position.enable && self.balancer() != ControllerKind::Lqr;
+        let setpoint = if cascade && self.is_balancing() {
+            self.position.setpoint(&state, self.setpoint, dt)
+        } else {
+            self.setpoint
+        };
+        self.u = if self.is_balancing() {
+            self.active_controller().control(&state, t, setpoint, dt)
+        } else {
+            self.swing_up_command(&state)
+        };
+        if self.push != 0. {
+            self.active_controller().reset();
+        }
+    }
 
-        let (s, c) = (th.sin(), th.cos());
-        let d = self.m2 * self.l * self.l * self.m1 - self.m3 * self.m3 * self.l * self.l * c * c;
-        let f2 = -self.m3 * self.m3 * self.l * self.l * w * w * s * c
-            + self.m3 * self.l * self.b1 * v * c
-            - self.m1 * (self.m3 * self.g * self.l * s + self.b2 * w);
-        let f4 = self.m2 * self.m3 * self.l * self.l * self.l * w * w * s
-            - self.m2 * self.l * self.l * self.b1 * v
-            + self.m3 * self.m3 * self.l * self.l * self.g * s * c
-            + self.m3 * self.l * self.b2 * w * c;
+    fn integrate(&mut self, dt: f64) {
+        let mut y = self.state.to_vector();
+        let mut adaptive = self.adaptive;
+        self.integrator
+            .integrate(&mut adaptive, 3, &mut y, dt, |y| self.rates(y));
+        self.adaptive = adaptive;
+        self.state = State::from_vector(y);
+        if self.motor.enable && self.motor.settles_within(self.timestep()) {
+            let r = self.params.drive_radius(self.plant);
+            self.state.i = self.motor.steady_current(self.V, self.state.v, r);
+        }
+    }
+
+    /// The stabilizing law in use, which for swing-up is the one it hands over to
+    pub fn balancer(&self) -> ControllerKind {
+        match self.controller {
+            ControllerKind::SwingUp => self.swing_up.balancer,
+            kind => kind,
+        }
+    }
+
+    pub fn is_balancing(&self) -> bool {
+        self.controller != ControllerKind::SwingUp || self.swing_up.balancing
+    }
 
-        // returns (vdot, v, wdot, w)
-        (
-            (f4 + self.m2 * self.l * self.l * self.F) / d,
+    /// The stabilizing law, which swing-up hands over to
+    pub fn active_controller(&mut self) -> &mut dyn Controller {
+        match (self.balancer(), &mut self.custom) {
+            (ControllerKind::Custom, Some(custom)) => custom.as_mut(),
+            (ControllerKind::Lqr, _) => &mut self.lqr,
+            _ => &mut self.pid,
+        }
+    }
+
+    /// The swing-up law's acceleration as a force on the cart or torque on the
+    /// arm, or as the voltage settling to it when the motor drives
+    fn swing_up_command(&self, state: &State) -> f64 {
+        let p = &self.params;
+        let upright = State::from(0., 0., 0., PI);
+        let (energy, target) = (self.swing_energy(state), self.potential_energy(&upright));
+        let a = self.swing_up.acceleration(state, energy, target, p.g);
+        let force = match self.plant {
+            // torque for an arm tip acceleration of a, with x read as the arm angle
+            Plant::Furuta => p.arm_inertia() / p.l_arm * a,
+            // the cart and wheels are much heavier than the pendulum, so F ≈ m1 a
+            _ => p.m1() * a,
+        };
+        if self.motor.enable {
+            self.motor
+                .voltage_for(force, state.v, p.drive_radius(self.plant))
+        } else {
+            force
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = State::default();
+        self.previous = self.state;
+        self.time = 0.;
+        self.accumulator = 0.;
+        self.next_sample = 0.;
+        self.V = 0.;
+        self.load = [0.; 2];
+        self.adaptive.reset();
+        self.reset_energy_drift();
+        self.pid.reset();
+        self.swing_up.reset();
+        self.position.reset();
+        self.track.reset();
+        self.actuator.reset();
+        self.sensors.reset();
+        self.observer.reset();
+        self.measured = self.state;
+        if let Some(custom) = &mut self.custom {
+            custom.reset();
+        }
+    }
+
+    /// Position of the first bob in the plane it swings in, from the pivot's
+    /// height at `x = 0`; the Furuta pendulum is seen from the front
+    pub fn bob(&self, y: &[f64; 6]) -> [f64; 2] {
+        let (x, th, l) = (y[0], y[2], self.params.l);
+        let (s, c) = (libm::sin(th), libm::cos(th));
+        match self.plant {
+            // the swing is normal to the arm, so it foreshortens as the arm turns
+            Plant::Furuta => [
+                self.params.l_arm * libm::sin(x) + l * s * libm::cos(x),
+                -l * c,
+            ],
+            _ => [x + l * s, -l * c],
+        }
+    }
+
+    /// Derivatives of the bob position with respect to `(x, th)`
+    fn bob_jacobian(&self, y: &[f64; 6]) -> Matrix<2, 2> {
+        let eps = 1e-6;
+        let mut j = zeros();
+        for (col, k) in [0, 2].into_iter().enumerate() {
+            let (mut hi, mut lo) = (*y, *y);
+            hi[k] += eps;
+            lo[k] -= eps;
+            let (hi, lo) = (self.bob(&hi), self.bob(&lo));
+            for i in 0..2 {
+                j[i][col] = (hi[i] - lo[i]) / (2. * eps);
+            }
+        }
+        j
+    }
+
+    /// Moves the first link to `th` at rest, for placing it by hand while paused
+    pub fn place(&mut self, th: f64) {
+        self.state.th = th;
+        self.state.w = 0.;
+        self.state.normalize();
+        self.previous = self.state;
+        self.reset_energy_drift();
+    }
+
+    /// Applies the impulse of an end stop impact, which also jolts the links
+    /// since only the cart is stopped
+    fn collide(&mut self) {
+        if self.plant == Plant::Furuta {
+            return;
+        }
+        let Some(dv) = self.track.collide(&mut self.state) else {
+            return;
+        };
+        let p = &self.params;
+        let s = &mut self.state;
+        // the impulse acts on x alone, so the links' generalized momenta are unchanged
+        let (dw, dw2) = match self.plant {
+            Plant::Double => {
+                let (l, l_2) = (p.l, p.l_2);
+                let (j1, a1) = ((p.m2() + p.m4()) * l * l, (p.m3() + p.m4()) * l);
+                let (j2, a2, j12) = (p.m5() * l_2 * l_2, p.m6() * l_2, p.m6() * l * l_2);
+                let c12 = libm::cos(s.th - s.th2);
+                let mass = [[j1, j12 * c12], [j12 * c12, j2]];
+                let momentum = [-a1 * libm::cos(s.th) * dv, -a2 * libm::cos(s.th2) * dv];
+                let [dw, dw2] = mul_vec(&inverse(&mass).unwrap_or_default(), &momentum);
+                (dw, dw2)
+            }
+            _ => (-p.m3() * libm::cos(s.th) * dv / (p.m2() * p.l), 0.),
+        };
+        s.v += dv;
+        s.w += dw;
+        s.w2 += dw2;
+    }
+
+    /// Time derivative of `State::to_vector()` under the applied force and voltage
+    pub fn rates(&self, y: &[f64; 7]) -> [f64; 7] {
+        let (mechanical, i) = ([y[0], y[1], y[2], y[3], y[4], y[5]], y[6]);
+        let r = self.params.drive_radius(self.plant);
+        let (force, di) = if self.motor.enable && self.motor.settles_within(self.timestep()) {
+            // too fast to step, so the current is held settled throughout
+            (self.F + self.motor.steady_force(self.V, y[1], r), 0.)
+        } else if self.motor.enable {
+            (
+                self.F + self.motor.force(i, r),
+                self.motor.current_rate(i, self.V, y[1], r),
+            )
+        } else {
+            (self.F, 0.)
+        };
+        let [mut fx, mut fth] = self.load;
+        if self.hand.target.is_some() {
+            let (p, j) = (self.bob(&mechanical), self.bob_jacobian(&mechanical));
+            let velocity = [0, 1].map(|i| j[i][0] * y[1] + j[i][1] * y[3]);
+            let f = self.hand.force(p, velocity, self.params.m2());
+            fx += j[0][0] * f[0] + j[1][0] * f[1];
+            fth += j[0][1] * f[0] + j[1][1] * f[1];
+        }
+        let [xdot, vdot, thdot, wdot, th2dot, w2dot] =
+            self.derivatives(&mechanical, [force + fx, fth]);
+        [xdot, vdot, thdot, wdot, th2dot, w2dot, di]
+    }
+
+    /// Force on the cart, or torque on the arm, including the motor's
+    pub fn force(&self) -> f64 {
+        if self.motor.enable {
+            let r = self.params.drive_radius(self.plant);
+            self.F + self.motor.force(self.state.i, r)
+        } else {
+            self.F
+        }
+    }
+
+    /// Jacobians of the first `N` entries of `(x, v, th, w, th2, w2)` with
+    /// respect to themselves and the controller output, by central differences
+    /// about the upright equilibrium. Dry friction is left out, having no
+    /// derivative at rest, and the motor current is taken as settled.
+    pub fn linearize<const N: usize>(&self) -> (Matrix<N, N>, Matrix<N, 1>) {
+        let eps = 1e-6;
+        let upright = [0., 0., PI, 0., PI, 0.];
+        let r = self.params.drive_radius(self.plant);
+        let rates = |y: &[f64; 6], u: f64| {
+            let force = if self.motor.enable {
+                self.motor.steady_force(u, y[1], r)
+            } else {
+                u
+            };
+            self.dynamics(y, [force, 0., 0.])
+        };
+        let mut a = zeros();
+        for j in 0..N {
+            let (mut hi, mut lo) = (upright, upright);
+            hi[j] += eps;
+            lo[j] -= eps;
+            let (hi, lo) = (rates(&hi, 0.), rates(&lo, 0.));
+            for i in 0..N {
+                a[i][j] = (hi[i] - lo[i]) / (2. * eps);
+            }
+        }
+        let (hi, lo) = (rates(&upright, eps), rates(&upright, -eps));
+        let b = std::array::from_fn(|i| [(hi[i] - lo[i]) / (2. * eps)]);
+        (a, b)
+    }
+
+    /// Rates under force `F` on the cart and torque `T` on the first link, with friction
+    pub fn derivatives(&self, y: &[f64; 6], [F, T]: [f64; 2]) -> [f64; 6] {
+        let [fx, fth] = self.friction.forces(y[1], y[3], |[fx, fth]| {
+            let rates = self.dynamics(y, [F + fx, T + fth, 0.]);
+            [rates[1], rates[3]]
+        });
+        self.dynamics(y, [F + fx, T + fth, 0.])
+    }
+
+    /// Rates under generalized forces on `(x, th, th2)` on top of gravity and drag
+    pub fn dynamics(&self, y: &[f64; 6], forces: [f64; 3]) -> [f64; 6] {
+        match self.plant {
+            Plant::Single => self.single_derivatives(y, forces),
+            Plant::Double => self.double_derivatives(y, forces),
+            Plant::Furuta => self.furuta_derivatives(y, forces),
+        }
+    }
+
+    fn single_derivatives(&self, &[_, v, th, w, _, _]: &[f64; 6], [F, T, _]: [f64; 3]) -> [f64; 6] {
+        let CartParams { l, b1, b2, g, .. } = self.params;
+        let (m1, m2, m3) = (self.params.m1(), self.params.m2(), self.params.m3());
+
+        // libm instead of the platform maths library keeps trajectories bit-identical across hosts
+        let (s, c) = (libm::sin(th), libm::cos(th));
+        let d = m2 * l * l * m1 - m3 * m3 * l * l * c * c;
+        let f2 =
+            -m3 * m3 * l * l * w * w * s * c + m3 * l * b1 * v * c - m1 * (m3 * g * l * s + b2 * w);
+        let f4 = m2 * m3 * l * l * l * w * w * s - m2 * l * l * b1 * v
+            + m3 * m3 * l * l * g * s * c
+            + m3 * l * b2 * w * c;
+
+        [
             v,
-            (f2 - self.m3 * self.l * c * self.F) / d,
+            (f4 + m2 * l * l * F - m3 * l * c * T) / d,
             w,
-        )
+            (f2 - m3 * l * c * F + m1 * T) / d,
+            0.,
+            0.,
+        ]
+    }
+
+    /// Lagrange's equations in `(x, th, th2)`, solved for the accelerations
+    /// through the mass matrix
+    fn double_derivatives(
+        &self,
+        &[_, v, th, w, th2, w2]: &[f64; 6],
+        [F, T, T2]: [f64; 3],
+    ) -> [f64; 6] {
+        let p = &self.params;
+        let CartParams {
+            l,
+            l_2,
+            b1,
+            b2,
+            b3,
+            g,
+            ..
+        } = *p;
+        // the second link's mass rides on the cart and on the tip of the first
+        let m1 = p.m1() + p.m4();
+        let (j1, a1) = ((p.m2() + p.m4()) * l * l, (p.m3() + p.m4()) * l);
+        let (j2, a2, j12) = (p.m5() * l_2 * l_2, p.m6() * l_2, p.m6() * l * l_2);
+
+        let (s1, c1) = (libm::sin(th), libm::cos(th));
+        let (s2, c2) = (libm::sin(th2), libm::cos(th2));
+        let (s12, c12) = (libm::sin(th - th2), libm::cos(th - th2));
+        let mass = [
+            [m1, a1 * c1, a2 * c2],
+            [a1 * c1, j1, j12 * c12],
+            [a2 * c2, j12 * c12, j2],
+        ];
+        let forces = [
+            F - b1 * v + a1 * s1 * w * w + a2 * s2 * w2 * w2,
+            T - a1 * g * s1 - j12 * s12 * w2 * w2 - b2 * w + b3 * (w2 - w),
+            T2 - a2 * g * s2 + j12 * s12 * w * w - b3 * (w2 - w),
+        ];
+        // the mass matrix is only singular when the links are massless
+        let [vdot, wdot, w2dot] = mul_vec(&inverse(&mass).unwrap_or_default(), &forces);
+        [v, vdot, w, wdot, w2, w2dot]
+    }
+
+    /// Lagrange's equations in the arm angle `x` and pendulum angle `th`, the
+    /// pendulum swinging in the vertical plane normal to the arm and the cart
+    /// drag acting on the arm
+    fn furuta_derivatives(&self, &[_, v, th, w, _, _]: &[f64; 6], [F, T, _]: [f64; 3]) -> [f64; 6] {
+        let p = &self.params;
+        let CartParams {
+            l,
+            l_arm,
+            b1,
+            b2,
+            g,
+            ..
+        } = *p;
+        let (j2, a) = (p.m2() * l * l, p.m3() * l * l_arm);
+        let (s, c) = (libm::sin(th), libm::cos(th));
+        let mass = [[p.arm_inertia() + j2 * s * s, a * c], [a * c, j2]];
+        let forces = [
+            F - b1 * v - 2. * j2 * s * c * v * w + a * s * w * w,
+            T + j2 * s * c * v * v - p.m3() * g * l * s - b2 * w,
+        ];
+        let [vdot, wdot] = mul_vec(&inverse(&mass).unwrap_or_default(), &forces);
+        [v, vdot, w, wdot, 0., 0.]
     }
 
     pub fn get_potential_energy(&self) -> f64 {
-        // with respect to ground
-        -self.m3 * self.g * self.l * self.state.th.cos()
+        self.potential_energy(&self.state)
     }
     pub fn get_kinetic_energy(&self) -> f64 {
-        0.5 * self.m1 * self.state.v * self.state.v
-            + 0.5 * self.m2 * self.state.w * self.state.w * self.l * self.l
-            + self.m3 * self.state.v * self.state.w * self.l * self.state.th.cos()
+        self.kinetic_energy(&self.state)
     }
     pub fn get_total_energy(&self) -> f64 {
         self.get_potential_energy() + self.get_kinetic_energy()
     }
 
-    pub fn display(
-        &self,
-        back_color: Color,
-        color: Color,
-        thickness: f32,
-        length: f32,
-        depth: f32,
-    ) {
-        draw_line(-length, -depth, length, -depth, thickness, color);
-        let x = (self.state.x - self.camera.y) as f32 * self.ui_scale;
-        let R = self.R as f32 * self.ui_scale;
-        let (c, s) = (
-            (self.state.x / self.R).cos() as f32,
-            (self.state.x / self.R).sin() as f32,
-        );
-
-        let ticks = (9. / self.ui_scale) as i32;
-        let gap = 2. / ticks as f32;
-        let offset = (self.camera.y as f32 * self.ui_scale) % gap;
-        for i in 0..ticks + 2 {
-            draw_line(
-                (-offset + gap * i as f32 - 1.) * length,
-                -depth - 0.002,
-                (-offset + gap * i as f32 - 1.) * length - 0.1 * self.ui_scale,
-                -depth - 0.1 * self.ui_scale,
-                thickness,
-                color,
+    /// Energy the swing-up pumps: the total less the kinetic energy of the
+    /// momentum along `x`, which the drive exchanges freely with the pendulum
+    pub fn swing_energy(&self, s: &State) -> f64 {
+        let (p, c) = (&self.params, libm::cos(s.th));
+        let (inertia, momentum) = match self.plant {
+            Plant::Single => (p.m1(), p.m1() * s.v + p.m3() * p.l * s.w * c),
+            Plant::Double => {
+                let mass = p.m1() + p.m4();
+                let links =
+                    (p.m3() + p.m4()) * p.l * s.w * c + p.m6() * p.l_2 * s.w2 * libm::cos(s.th2);
+                (mass, mass * s.v + links)
+            }
+            Plant::Furuta => {
+                let sin = libm::sin(s.th);
+                let inertia = p.arm_inertia() + p.m2() * p.l * p.l * sin * sin;
+                (inertia, inertia * s.v + p.m3() * p.l * p.l_arm * s.w * c)
+            }
+        };
+        self.potential_energy(s) + self.kinetic_energy(s) - 0.5 * momentum * momentum / inertia
+    }
+
+    fn potential_energy(&self, s: &State) -> f64 {
+        // with respect to ground
+        let p = &self.params;
+        let single = -p.m3() * p.g * p.l * libm::cos(s.th);
+        match self.plant {
+            Plant::Single | Plant::Furuta => single,
+            Plant::Double => {
+                single
+                    - p.m4() * p.g * p.l * libm::cos(s.th)
+                    - p.m6() * p.g * p.l_2 * libm::cos(s.th2)
+            }
+        }
+    }
+    fn kinetic_energy(&self, s: &State) -> f64 {
+        let p = &self.params;
+        let single = 0.5 * p.m1() * s.v * s.v
+            + 0.5 * p.m2() * s.w * s.w * p.l * p.l
+            + p.m3() * s.v * s.w * p.l * libm::cos(s.th);
+        match self.plant {
+            Plant::Single => single,
+            Plant::Double => {
+                // the second link's mass moving with the first link's tip
+                let tip = 0.5 * p.m4() * (s.v * s.v + s.w * s.w * p.l * p.l)
+                    + p.m4() * s.v * s.w * p.l * libm::cos(s.th);
+                single
+                    + tip
+                    + 0.5 * p.m5() * s.w2 * s.w2 * p.l_2 * p.l_2
+                    + p.m6() * s.v * s.w2 * p.l_2 * libm::cos(s.th2)
+                    + p.m6() * s.w * s.w2 * p.l * p.l_2 * libm::cos(s.th - s.th2)
+            }
+            Plant::Furuta => {
+                let sin = libm::sin(s.th);
+                0.5 * (p.arm_inertia() + p.m2() * p.l * p.l * sin * sin) * s.v * s.v
+                    + p.m3() * p.l * p.l_arm * s.v * s.w * libm::cos(s.th)
+                    + 0.5 * p.m2() * s.w * s.w * p.l * p.l
+            }
+        }
+    }
+    /// Change in total energy since reset, which only the integrator causes
+    /// while the controller is off and both drags are zero
+    pub fn get_energy_drift(&self) -> f64 {
+        self.get_total_energy() - self.energy_reference
+    }
+
+    pub fn reset_energy_drift(&mut self) {
+        self.energy_reference = self.get_total_energy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{controller::DerivativeSource, friction::FrictionModel};
+
+    /// Plays `seconds` of 60 Hz frames
+    fn run(cart: &mut Cart, seconds: f64) {
+        for _ in 0..(seconds * 60.).round() as usize {
+            cart.update(1. / 60.);
+        }
+    }
+
+    fn upright(cart: &Cart, tol: f64) -> bool {
+        wrap_angle(cart.state.th - PI).abs() < tol
+    }
+
+    fn vdot_wdot_with(edit: impl Fn(&mut CartParams)) -> (f64, f64) {
+        let mut params = CartParams::default();
+        edit(&mut params);
+        let cart = Cart {
+            F: 10.,
+            params,
+            ..Default::default()
+        };
+        let [_, vdot, _, wdot, ..] = cart.rates(&State::from(0., 0.5, -0.3, PI + 0.2).to_vector());
+        (vdot, wdot)
+    }
+
+    #[test]
+    fn zeroed_masses_keep_the_dynamics_finite() {
+        for plant in [Plant::Single, Plant::Double, Plant::Furuta] {
+            let mut cart = Cart {
+                plant,
+                enable: false,
+                ..Default::default()
+            };
+            let p = &mut cart.params;
+            p.set_cart_mass(0.);
+            p.set_wheel_mass(0.);
+            p.set_bob_mass(0.);
+            p.set_rod_mass(0.);
+            p.set_second_bob_mass(0.);
+            p.set_second_rod_mass(0.);
+            assert!(p.m + p.ml >= CartParams::MIN_MASS);
+            assert!(p.m_2 + p.ml_2 >= CartParams::MIN_MASS);
+            run(&mut cart, 2.);
+            assert!(cart.state.to_vector().iter().all(|y| y.is_finite()));
+        }
+    }
+
+    #[test]
+    fn every_mass_feeds_the_dynamics() {
+        let base = vdot_wdot_with(|_| ());
+        let edits: [(_, fn(&mut CartParams)); 4] = [
+            ("M_cart", |p| p.M *= 2.),
+            ("M_bob", |p| p.m *= 2.),
+            ("M_rod", |p| p.ml *= 2.),
+            ("M_wheel", |p| p.mw *= 2.),
+        ];
+        for (name, edit) in edits {
+            let (vdot, wdot) = vdot_wdot_with(edit);
+            assert!(
+                (vdot - base.0).abs() > 1e-6 && (wdot - base.1).abs() > 1e-6,
+                "changing {name} did not change the dynamics"
             );
         }
-        draw_rectangle(
-            -1.,
-            -depth - 0.001,
-            1. - length - 0.003,
-            -0.11 * self.ui_scale,
-            back_color,
-        );
-        draw_rectangle(
-            length + 0.003,
-            -depth - 0.001,
-            1. - length - 0.003,
-            -0.11 * self.ui_scale,
-            back_color,
-        );
-
-        let (w, h) = (R * 10., R * 3.5);
-        // cart
-        draw_rectangle_lines(x - 0.5 * w, -depth + 2. * R, w, h, thickness * 2., color);
-
-        // wheels
-        draw_circle_lines(x - 0.30 * w, -depth + R, R, thickness, color);
-        draw_circle_lines(x + 0.30 * w, -depth + R, R, thickness, color);
-        draw_line(
-            x - 0.30 * w,
-            -depth + R,
-            x - 0.30 * w - R * c,
-            -depth + R + R * s,
-            thickness,
-            color,
-        );
-        draw_line(
-            x + 0.30 * w,
-            -depth + R,
-            x + 0.30 * w - R * c,
-            -depth + R + R * s,
-            thickness,
-            color,
-        );
-
-        let (c, s) = ((self.state.th).cos() as f32, (self.state.th).sin() as f32);
-        let l = self.l as f32 * self.ui_scale;
-        // pendulum
-        draw_line(
-            x,
-            -depth + h + 2. * R,
-            x + (l - R) * s,
-            -depth + h + 2. * R - (l - R) * c,
-            thickness,
-            color,
-        );
-        draw_circle_lines(x + l * s, -depth + h + 2. * R - l * c, R, thickness, color);
-        draw_circle(x, -depth + 2. * R + h, 0.01, color);
+    }
+
+    #[test]
+    fn balances_headless() {
+        let mut cart = Cart::default();
+        run(&mut cart, 10.);
+        assert!(upright(&cart, 0.01));
+    }
+
+    #[test]
+    fn custom_controller_drives_the_cart() {
+        struct Constant;
+        impl Controller for Constant {
+            fn control(&mut self, _: &State, _: f64, _: f64, _: f64) -> f64 {
+                5.
+            }
+        }
+        let mut cart = Cart {
+            controller: ControllerKind::Custom,
+            custom: Some(Box::new(Constant)),
+            ..Default::default()
+        };
+        cart.update(1. / 60.);
+        assert_eq!(cart.F, 5.);
+    }
+
+    #[test]
+    fn lqr_returns_the_cart_home() {
+        let mut cart = Cart {
+            controller: ControllerKind::Lqr,
+            state: State::from(1., 0., 0., PI + 0.2),
+            ..Default::default()
+        };
+        run(&mut cart, 20.);
+        assert!(cart.lqr.solved);
+        assert!(cart.state.x.abs() < 0.01, "x = {}", cart.state.x);
+        assert!(upright(&cart, 0.01));
+    }
+
+    #[test]
+    fn swing_up_from_hanging() {
+        // the double pendulum is only caught with both links upright
+        for plant in [Plant::Single, Plant::Double, Plant::Furuta] {
+            let mut cart = Cart {
+                plant,
+                controller: ControllerKind::SwingUp,
+                state: State::from(0., 0., 0., 0.1),
+                ..Default::default()
+            };
+            run(&mut cart, 30.);
+            assert!(cart.swing_up.balancing);
+            assert!(upright(&cart, 0.01), "th = {}", cart.state.th);
+            if plant == Plant::Double {
+                assert!(wrap_angle(cart.state.th2 - PI).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn position_loop_moves_the_cart_to_target() {
+        for controller in [ControllerKind::Pid, ControllerKind::Lqr] {
+            let mut cart = Cart {
+                controller,
+                state: State::from(0., 0., 0., PI),
+                ..Default::default()
+            };
+            cart.position.enable = true;
+            cart.position.target = 1.;
+            run(&mut cart, 30.);
+            assert!((cart.state.x - 1.).abs() < 0.05, "x = {}", cart.state.x);
+        }
+    }
+
+    #[test]
+    fn discrete_controller_holds_its_output() {
+        let mut cart = Cart {
+            discrete: true,
+            sample_rate: 50.,
+            ..Default::default()
+        };
+        let mut changes = 0;
+        let mut last = cart.F;
+        for _ in 0..600 {
+            cart.update(1. / 600.);
+            if cart.F != last {
+                changes += 1;
+                last = cart.F;
+            }
+        }
+        assert_eq!(changes, 50);
+
+        cart.sample_rate = 5.;
+        run(&mut cart, 0.1);
+        cart.sample_rate = 1000.;
+        cart.update(1. / 60.);
+        assert!(cart.next_sample - cart.time <= 1e-3 + 1e-12);
+    }
+
+    #[test]
+    fn trajectory_is_independent_of_frame_rate() {
+        let simulate = |frame: f64, frames: usize| {
+            let mut cart = Cart::default();
+            for _ in 0..frames {
+                cart.update(frame);
+            }
+            (cart.time, cart.state)
+        };
+        let (t60, s60) = simulate(1. / 60., 240);
+        let (t24, s24) = simulate(1. / 24., 96);
+        assert_eq!(t60.to_bits(), t24.to_bits());
+        for (a, b) in [
+            (s60.x, s24.x),
+            (s60.v, s24.v),
+            (s60.th, s24.th),
+            (s60.w, s24.w),
+        ] {
+            assert_eq!(a.to_bits(), b.to_bits());
+        }
+    }
+
+    #[test]
+    fn reset_replays_a_fresh_run() {
+        let sampled = || {
+            let mut cart = Cart {
+                state: State::from(0., 0., 0., PI + 0.2),
+                ..Default::default()
+            };
+            cart.pid.derivative_source = DerivativeSource::Sampled;
+            cart
+        };
+        let mut cart = sampled();
+        run(&mut cart, 1.);
+        cart.reset();
+        let mut fresh = sampled();
+        fresh.reset();
+        run(&mut cart, 1.);
+        run(&mut fresh, 1.);
+        assert!(cart.state == fresh.state && cart.pid == fresh.pid);
+    }
+
+    #[test]
+    fn dormand_prince_conserves_energy() {
+        let mut cart = Cart {
+            enable: false,
+            integrator: IntegratorKind::DormandPrince,
+            state: State::from(0., 0., 0., 1.),
+            ..Default::default()
+        };
+        cart.params.b1 = 0.;
+        cart.params.b2 = 0.;
+        cart.adaptive.rtol = 1e-9;
+        cart.adaptive.atol = 1e-9;
+        let energy = cart.get_total_energy();
+        run(&mut cart, 10.);
+        assert!((cart.get_total_energy() - energy).abs() < 1e-6);
+        assert!(cart.adaptive.accepted > 0 && !cart.adaptive.stalled);
+    }
+
+    #[test]
+    fn symplectic_schemes_keep_energy_bounded() {
+        let drift = |integrator| {
+            let mut cart = Cart {
+                enable: false,
+                integrator,
+                state: State::from(0., 0., 0., 1.),
+                ..Default::default()
+            };
+            cart.params.b1 = 0.;
+            cart.params.b2 = 0.;
+            cart.reset_energy_drift();
+            run(&mut cart, 60.);
+            cart.get_energy_drift().abs()
+        };
+        let euler = drift(IntegratorKind::Euler);
+        for integrator in [
+            IntegratorKind::SemiImplicitEuler,
+            IntegratorKind::VelocityVerlet,
+            IntegratorKind::Leapfrog,
+        ] {
+            assert!(drift(integrator) < 0.05 * euler);
+        }
+    }
+
+    #[test]
+    fn energy_tracks_parameter_edits() {
+        let mut cart = Cart {
+            state: State::from(0., 1., 1., PI + 0.5),
+            ..Default::default()
+        };
+        let before = cart.get_total_energy();
+        cart.params.m += 1.;
+        assert!(cart.get_total_energy() != before);
+    }
+
+    #[test]
+    fn double_pendulum_conserves_energy_and_balances() {
+        let mut cart = Cart {
+            plant: Plant::Double,
+            enable: false,
+            params: CartParams {
+                b1: 0.,
+                b2: 0.,
+                b3: 0.,
+                ..Default::default()
+            },
+            state: State::from(0., 0., 0., 2.).with_second_link(1., 0.),
+            ..Default::default()
+        };
+        cart.reset_energy_drift();
+        run(&mut cart, 10.);
+        assert!(cart.get_energy_drift().abs() < 1e-4);
+
+        let mut cart = Cart {
+            plant: Plant::Double,
+            controller: ControllerKind::Lqr,
+            state: State::from(0., 0., 0., PI + 0.05).with_second_link(PI - 0.05, 0.),
+            ..Default::default()
+        };
+        run(&mut cart, 10.);
+        let s = cart.state;
+        assert!(upright(&cart, 1e-3) && wrap_angle(s.th2 - PI).abs() < 1e-3);
+        assert!(s.x.abs() < 1e-2);
+    }
+
+    #[test]
+    fn furuta_conserves_energy() {
+        let mut cart = Cart {
+            plant: Plant::Furuta,
+            enable: false,
+            params: CartParams {
+                b1: 0.,
+                b2: 0.,
+                ..Default::default()
+            },
+            state: State::from(0., 1., 0., 2.),
+            ..Default::default()
+        };
+        cart.reset_energy_drift();
+        run(&mut cart, 10.);
+        assert!(cart.get_energy_drift().abs() < 1e-4);
+    }
+
+    #[test]
+    fn elastic_end_stops_keep_the_cart_and_its_energy() {
+        let mut cart = Cart {
+            enable: false,
+            params: CartParams {
+                b1: 0.,
+                b2: 0.,
+                ..Default::default()
+            },
+            state: State::from(0., 2., 1., 2.),
+            ..Default::default()
+        };
+        cart.track.enable = true;
+        cart.track.restitution = 1.;
+        cart.reset_energy_drift();
+        for _ in 0..600 {
+            cart.update(1. / 60.);
+            assert!(cart.state.x.abs() <= 0.5 * cart.track.length);
+        }
+        assert!(cart.track.hits > 0);
+        assert!(cart.get_energy_drift().abs() < 1e-4);
+    }
+
+    #[test]
+    fn stick_slip_holds_below_breakaway() {
+        for (push, moves) in [(2.5, false), (5., true)] {
+            let mut cart = Cart {
+                enable: false,
+                push: 1.,
+                Finp: push,
+                state: State::from(0., 0., 0., 0.),
+                ..Default::default()
+            };
+            cart.friction.model = FrictionModel::Stribeck;
+            cart.friction.stick_slip = true;
+            run(&mut cart, 2.);
+            assert_eq!(cart.state.x.abs() > 0.1, moves, "push of {push} N");
+        }
+    }
+
+    #[test]
+    fn lqr_balances_through_the_motor() {
+        // down to inductances whose current settles faster than a tick
+        for La in [0.01, 1e-3, 1e-4] {
+            let mut cart = Cart {
+                controller: ControllerKind::Lqr,
+                ..Default::default()
+            };
+            cart.motor.enable = true;
+            cart.motor.La = La;
+            let mut peak: f64 = 0.;
+            for _ in 0..600 {
+                cart.update(1. / 60.);
+                peak = peak.max(cart.state.i.abs());
+                assert!(cart.V.abs() <= cart.motor.v_max);
+            }
+            assert!(peak > 1., "the current never moved with L_a {La}");
+            assert!(upright(&cart, 1e-3) && cart.state.x.abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn long_dead_time_topples_the_lqr() {
+        for (delay, holds) in [(0.02, true), (0.2, false)] {
+            let mut cart = Cart {
+                controller: ControllerKind::Lqr,
+                ..Default::default()
+            };
+            cart.actuator.delay = delay;
+            run(&mut cart, 20.);
+            assert_eq!(upright(&cart, 0.01), holds, "dead time of {delay} s");
+        }
+    }
+
+    #[test]
+    fn seeded_sensors_replay_exactly() {
+        let replay = || {
+            let mut cart = Cart::default();
+            cart.sensors.enable = true;
+            cart.sensors.cpr = 4096;
+            cart.reset();
+            run(&mut cart, 2.);
+            cart
+        };
+        let (a, b) = (replay(), replay());
+        assert!(a.state == b.state && a.measured == b.measured);
+        assert!(a.measured.th != a.state.th);
+    }
+
+    #[test]
+    fn observers_balance_through_sensor_noise() {
+        // differencing the noisy readings topples the LQR, the observers hold it
+        for (source, holds) in [
+            (Estimator::Measured, false),
+            (Estimator::Luenberger, true),
+            (Estimator::Kalman, true),
+        ] {
+            let mut cart = Cart {
+                controller: ControllerKind::Lqr,
+                ..Default::default()
+            };
+            cart.sensors.enable = true;
+            cart.sensors.position.noise = 1e-2;
+            cart.sensors.angle.noise = 1e-2;
+            cart.observer.source = source;
+            cart.reset();
+            run(&mut cart, 20.);
+            assert_eq!(upright(&cart, 0.05), holds, "estimator {}", source as u8);
+        }
+    }
+
+    #[test]
+    fn scripted_disturbances_replay_and_are_rejected() {
+        let replay = || {
+            let mut cart = Cart {
+                controller: ControllerKind::Lqr,
+                ..Default::default()
+            };
+            cart.disturbances = Disturbances {
+                enable: true,
+                list: Disturbances::parse("1 cart impulse 20 0.01\n4 pendulum impulse 1 0.01")
+                    .unwrap(),
+            };
+            let mut peak: f64 = 0.;
+            for _ in 0..600 {
+                cart.update(1. / 60.);
+                peak = peak.max(wrap_angle(cart.state.th - PI).abs());
+            }
+            (cart, peak)
+        };
+        let ((a, peak), (b, _)) = (replay(), replay());
+        assert!(a.state == b.state);
+        assert!(peak > 0.05, "the kicks barely moved it, {peak}");
+        assert!(upright(&a, 1e-3) && a.state.x.abs() < 1e-2);
+    }
+
+    #[test]
+    fn dragged_bob_follows_the_hand_and_recovers_on_release() {
+        let mut cart = Cart {
+            controller: ControllerKind::Lqr,
+            ..Default::default()
+        };
+        let hand = [0.3, 0.95];
+        cart.hand.target = Some(hand);
+        run(&mut cart, 2.);
+        let [x, v, th, w, th2, w2, _] = cart.state.to_vector();
+        let [x, y] = cart.bob(&[x, v, th, w, th2, w2]);
+        assert!((x - hand[0]).hypot(y - hand[1]) < 0.1, "bob at {x}, {y}");
+        cart.hand.target = None;
+        run(&mut cart, 10.);
+        assert!(upright(&cart, 1e-3));
+    }
+
+    #[test]
+    fn pause_steps_and_time_scale() {
+        let mut cart = Cart {
+            paused: true,
+            ..Default::default()
+        };
+        cart.update(1. / 60.);
+        assert_eq!(cart.time, 0.);
+        cart.step_once();
+        assert!((cart.time - cart.timestep()).abs() < 1e-12);
+        assert!(cart.interpolated() == cart.state);
+
+        cart.reset();
+        cart.paused = false;
+        cart.time_scale = 0.5;
+        run(&mut cart, 1.);
+        assert!((cart.time - 0.5).abs() < 1e-9, "{}", cart.time);
     }
 }
@@ -0,0 +1,239 @@
+use crate::state::{wrap_angle, State};
+
+pub trait Controller {
+    /// Returns the force to apply to the cart, before clamping
+    fn control(&mut self, state: &State, t: f64, setpoint: f64, dt: f64) -> f64;
+
+    /// Clears any internal state, called on reset and while the cart is pushed manually
+    fn reset(&mut self) {}
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Default)]
+pub enum ControllerKind {
+    #[default]
+    Pid,
+    Lqr,
+    SwingUp,
+    Custom,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Default)]
+pub enum AntiWindup {
+    #[default]
+    None,
+    /// Keeps the integral within `±int_limit`
+    Clamping,
+    /// Stops integrating while the output is saturated and the error would push it further
+    Conditional,
+    /// Bleeds the integral by the saturation excess with time constant `tracking`
+    BackCalculation,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Default)]
+pub enum DerivativeSource {
+    /// The exact angular velocity from the simulation
+    #[default]
+    Velocity,
+    /// Differences of sampled angles through a first order low-pass filter
+    Sampled,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Pid {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub anti_windup: AntiWindup,
+    pub int_limit: f64,
+    pub tracking: f64,
+    pub derivative_source: DerivativeSource,
+    /// Differentiate the error, so setpoint changes kick the output, or only the measurement
+    pub derivative_on_error: bool,
+    /// Filter time constant is `kd / (kp N)`
+    pub filter_n: f64,
+    /// Actuator saturation, `Fclamp` or the motor's `v_max`
+    pub limit: f64,
+    pub error: f64,
+    pub int: f64,
+    pub derivative: f64,
+    last: Option<(f64, f64)>,
+}
+
+impl Default for Pid {
+    fn default() -> Self {
+        Self::new(400., 80., 25.)
+    }
+}
+
+impl Pid {
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Pid {
+            kp,
+            ki,
+            kd,
+            anti_windup: AntiWindup::default(),
+            int_limit: 1.,
+            tracking: 0.5,
+            derivative_source: DerivativeSource::default(),
+            derivative_on_error: false,
+            filter_n: 10.,
+            limit: f64::INFINITY,
+            error: 0.,
+            int: 0.,
+            derivative: 0.,
+            last: None,
+        }
+    }
+
+    pub fn filter_time_constant(&self) -> f64 {
+        if self.kp > 0. && self.filter_n > 0. {
+            self.kd / (self.kp * self.filter_n)
+        } else {
+            0.
+        }
+    }
+
+    fn update_derivative(&mut self, th: f64, setpoint: f64, dt: f64) {
+        let (last_th, last_setpoint) = self.last.unwrap_or((th, setpoint));
+        self.last = Some((th, setpoint));
+        let kick = if self.derivative_on_error {
+            (setpoint - last_setpoint) / dt
+        } else {
+            0.
+        };
+        let rate = -wrap_angle(th - last_th) / dt + kick;
+        let tf = self.filter_time_constant();
+        self.derivative += (rate - self.derivative) * dt / (tf + dt);
+    }
+}
+
+impl Controller for Pid {
+    fn control(&mut self, state: &State, _t: f64, setpoint: f64, dt: f64) -> f64 {
+        self.error = setpoint - state.th;
+        match self.derivative_source {
+            DerivativeSource::Velocity => {
+                let last_setpoint = self.last.map_or(setpoint, |(_, sp)| sp);
+                self.last = Some((state.th, setpoint));
+                self.derivative = -state.w;
+                if self.derivative_on_error {
+                    self.derivative += (setpoint - last_setpoint) / dt;
+                }
+            }
+            DerivativeSource::Sampled => self.update_derivative(state.th, setpoint, dt),
+        }
+        let pd = self.kp * self.error + self.kd * self.derivative;
+        let u = pd + self.ki * self.int;
+        match self.anti_windup {
+            AntiWindup::None => self.int += self.error * dt,
+            AntiWindup::Clamping => {
+                self.int = (self.int + self.error * dt).clamp(-self.int_limit, self.int_limit)
+            }
+            AntiWindup::Conditional => {
+                if u.abs() < self.limit || u.signum() != self.error.signum() {
+                    self.int += self.error * dt;
+                }
+            }
+            AntiWindup::BackCalculation => {
+                let excess = u.clamp(-self.limit, self.limit) - u;
+                let track = if self.ki > 0. {
+                    excess / (self.ki * self.tracking)
+                } else {
+                    0.
+                };
+                self.int += (self.error + track) * dt;
+            }
+        }
+        pd + self.ki * self.int
+    }
+
+    fn reset(&mut self) {
+        self.error = 0.;
+        self.int = 0.;
+        self.derivative = 0.;
+        self.last = None;
+    }
+}
+
+/// Outer loop on the cart position, which tilts the angle setpoint of the
+/// balancing controller so that the cart accelerates towards `target`
+#[derive(Clone, Copy, PartialEq)]
+pub struct PositionLoop {
+    pub enable: bool,
+    pub target: f64,
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub max_tilt: f64,
+    pub int: f64,
+}
+
+impl Default for PositionLoop {
+    fn default() -> Self {
+        PositionLoop {
+            enable: false,
+            target: 0.,
+            kp: 0.02,
+            ki: 0.,
+            kd: 0.05,
+            max_tilt: 0.2,
+            int: 0.,
+        }
+    }
+}
+
+impl PositionLoop {
+    pub fn setpoint(&mut self, state: &State, upright: f64, dt: f64) -> f64 {
+        let error = self.target - state.x;
+        self.int += error * dt;
+        let tilt = self.kp * error + self.ki * self.int - self.kd * state.v;
+        upright - tilt.clamp(-self.max_tilt, self.max_tilt)
+    }
+
+    pub fn reset(&mut self) {
+        self.int = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn anti_windup_bounds_the_integral() {
+        // held off upright with a saturated output for ten seconds
+        let integral = |anti_windup| {
+            let mut pid = Pid {
+                anti_windup,
+                limit: 20.,
+                ..Default::default()
+            };
+            let state = State::from(0., 0., 0., PI - 0.5);
+            for _ in 0..1000 {
+                pid.control(&state, 0., PI, 0.01);
+            }
+            pid.int
+        };
+        assert!((integral(AntiWindup::None) - 5.).abs() < 1e-9);
+        assert!((integral(AntiWindup::Clamping) - 1.).abs() < 1e-9);
+        assert_eq!(integral(AntiWindup::Conditional), 0.);
+        // settles where the tracking term cancels the error, 0.5 + (20 - 200 - 80 int) / 40 = 0
+        assert!((integral(AntiWindup::BackCalculation) + 2.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sampled_derivative_follows_the_rate() {
+        let mut pid = Pid {
+            derivative_source: DerivativeSource::Sampled,
+            ..Default::default()
+        };
+        // rotating through the 2π wrap at a steady 3 rad/s
+        let mut state = State::from(0., 0., 3., 2. * PI - 1.);
+        for _ in 0..100 {
+            pid.control(&state, 0., PI, 0.01);
+            state = State::from(0., 0., state.w, state.th + state.w * 0.01);
+            state.normalize();
+        }
+        assert!((pid.derivative + 3.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,271 @@
+//! Scheduled external loads, so every controller can be compared against the
+//! same disturbances.
+//!
+//! A scenario is plain text with one disturbance per line, `#` starting a
+//! comment, in whitespace separated columns:
+//!
+//! ```text
+//! # start  target    shape    amplitude  duration  [frequency  repeat]
+//! 1        cart      impulse  20         0.01
+//! 4        pendulum  step     2          0.5
+//! 8        cart      sine     10         4         0.5
+//! 12       pendulum  impulse  1          0.01      0          3
+//! ```
+
+use std::{f64::consts::PI, fmt, str::FromStr};
+
+/// Where a disturbance acts
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Target {
+    /// Force on the cart, or torque on the Furuta arm
+    #[default]
+    Cart,
+    /// Torque on the first link
+    Pendulum,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Shape {
+    /// `amplitude` of momentum spread evenly over `duration`
+    #[default]
+    Impulse,
+    /// Constant `amplitude` held for `duration`, which may be infinite
+    Step,
+    /// `amplitude` sine of `frequency` Hz for `duration`
+    Sine,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Disturbance {
+    pub start: f64,
+    pub target: Target,
+    pub shape: Shape,
+    pub amplitude: f64,
+    pub duration: f64,
+    pub frequency: f64,
+    /// Period the disturbance recurs with, 0 for once
+    pub repeat: f64,
+}
+
+impl Default for Disturbance {
+    fn default() -> Self {
+        Disturbance {
+            start: 1.,
+            target: Target::Cart,
+            shape: Shape::Impulse,
+            amplitude: 10.,
+            duration: 0.01,
+            frequency: 1.,
+            repeat: 0.,
+        }
+    }
+}
+
+impl Disturbance {
+    /// Mean load over `[t, t + h]`, exact however short the disturbance
+    pub fn average(&self, t: f64, h: f64) -> f64 {
+        let recurs = self.repeat > 0.;
+        let mut k = if recurs {
+            libm::floor((t - self.start) / self.repeat).max(0.)
+        } else {
+            0.
+        };
+        let mut total = 0.;
+        loop {
+            let on = self.start + k * self.repeat;
+            if on >= t + h {
+                break;
+            }
+            let (a, b) = (t.max(on), (t + h).min(on + self.duration));
+            if b > a {
+                total += match self.shape {
+                    Shape::Impulse => self.amplitude / self.duration * (b - a),
+                    Shape::Step => self.amplitude * (b - a),
+                    Shape::Sine if self.frequency > 0. => {
+                        let w = 2. * PI * self.frequency;
+                        self.amplitude * (libm::cos(w * (a - on)) - libm::cos(w * (b - on))) / w
+                    }
+                    Shape::Sine => 0.,
+                };
+            }
+            if !recurs {
+                break;
+            }
+            k += 1.;
+        }
+        total / h
+    }
+}
+
+impl Disturbance {
+    /// Rejects values `average` can't play back: anything not finite but an
+    /// endless duration, and repeats overlapping themselves, which would also
+    /// take many windows per tick
+    pub fn check(&self) -> Result<(), String> {
+        let finite = [
+            (self.start, "start"),
+            (self.amplitude, "amplitude"),
+            (self.frequency, "frequency"),
+            (self.repeat, "repeat"),
+        ];
+        if let Some((_, name)) = finite.iter().find(|(v, _)| !v.is_finite()) {
+            return Err(format!("{name} must be finite"));
+        }
+        if self.duration.is_nan() || self.duration <= 0. {
+            return Err("duration must be positive".to_string());
+        }
+        if self.repeat < 0. || self.frequency < 0. {
+            return Err("frequency and repeat can't be negative".to_string());
+        }
+        if self.repeat != 0. && self.repeat < self.duration {
+            return Err("repeat must be 0 or at least the duration".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Disturbance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let target = match self.target {
+            Target::Cart => "cart",
+            Target::Pendulum => "pendulum",
+        };
+        let shape = match self.shape {
+            Shape::Impulse => "impulse",
+            Shape::Step => "step",
+            Shape::Sine => "sine",
+        };
+        write!(
+            f,
+            "{} {target} {shape} {} {} {} {}",
+            self.start, self.amplitude, self.duration, self.frequency, self.repeat
+        )
+    }
+}
+
+impl FromStr for Disturbance {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if !(5..=7).contains(&fields.len()) {
+            return Err(format!("expected 5 to 7 columns, found {}", fields.len()));
+        }
+        let number = |i: usize, name: &str| match fields.get(i) {
+            None => Ok(0.),
+            Some(s) => s
+                .parse::<f64>()
+                .map_err(|_| format!("{name} `{s}` is not a number")),
+        };
+        let target = match fields[1] {
+            "cart" => Target::Cart,
+            "pendulum" => Target::Pendulum,
+            s => return Err(format!("unknown target `{s}`")),
+        };
+        let shape = match fields[2] {
+            "impulse" => Shape::Impulse,
+            "step" => Shape::Step,
+            "sine" => Shape::Sine,
+            s => return Err(format!("unknown shape `{s}`")),
+        };
+        let disturbance = Disturbance {
+            start: number(0, "start")?,
+            target,
+            shape,
+            amplitude: number(3, "amplitude")?,
+            duration: number(4, "duration")?,
+            frequency: number(5, "frequency")?,
+            repeat: number(6, "repeat")?,
+        };
+        disturbance.check()?;
+        Ok(disturbance)
+    }
+}
+
+/// The schedule the simulation plays back, its clock being the simulation time
+#[derive(Clone, PartialEq, Default)]
+pub struct Disturbances {
+    pub enable: bool,
+    pub list: Vec<Disturbance>,
+}
+
+impl Disturbances {
+    /// Mean force on the cart and torque on the first link over `[t, t + h]`
+    pub fn loads(&self, t: f64, h: f64) -> [f64; 2] {
+        let mut loads = [0.; 2];
+        if !self.enable {
+            return loads;
+        }
+        for d in &self.list {
+            loads[d.target as usize] += d.average(t, h);
+        }
+        loads
+    }
+
+    /// Reads a scenario, reporting the first bad line
+    pub fn parse(text: &str) -> Result<Vec<Disturbance>, String> {
+        text.lines()
+            .enumerate()
+            .map(|(n, line)| (n, line.split('#').next().unwrap_or_default().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(n, line)| line.parse().map_err(|e| format!("line {}: {e}", n + 1)))
+            .collect()
+    }
+}
+
+impl fmt::Display for Disturbances {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "# start target shape amplitude duration frequency repeat"
+        )?;
+        for d in &self.list {
+            writeln!(f, "{d}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_integrate_exactly_and_scenarios_round_trip() {
+        let text = "1 cart impulse 20 0.01 0 3\n2.5 pendulum sine 4 1 2 # two cycles\n";
+        let disturbances = Disturbances {
+            enable: true,
+            list: Disturbances::parse(text).unwrap(),
+        };
+        // ticks much longer than the impulse still deliver all of it, each time
+        let (h, mut momentum) = (0.03, [0.; 2]);
+        for n in 0..400 {
+            let loads = disturbances.loads(n as f64 * h, h);
+            momentum[0] += loads[0] * h;
+            momentum[1] += loads[1] * h;
+        }
+        assert!((momentum[0] - 4. * 20.).abs() < 1e-9, "{momentum:?}");
+        assert!(momentum[1].abs() < 1e-9, "{momentum:?}");
+
+        let saved = disturbances.to_string();
+        assert_eq!(Disturbances::parse(&saved).unwrap(), disturbances.list);
+        assert!(Disturbances::parse("1 cart kick 1 1").is_err());
+        for bad in [
+            "nan cart step 1 1 0 1",
+            "1 cart step nan 1",
+            "1 cart step 1 nan",
+            "1 cart sine 1 1 inf",
+            "1 cart step 1 1 0 nan",
+            "1 cart step 1 1 0 inf",
+            "1 cart step 1 1 0 1e-9",
+            "1 cart step 1 inf 0 5",
+        ] {
+            assert!(Disturbances::parse(bad).is_err(), "accepted `{bad}`");
+        }
+        let endless = Disturbances::parse("1 cart step 1 inf").unwrap();
+        assert_eq!(endless[0].duration, f64::INFINITY);
+        assert!(Disturbances::parse("\n1 cart step 1 0")
+            .unwrap_err()
+            .starts_with("line 2"));
+    }
+}
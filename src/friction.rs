@@ -0,0 +1,123 @@
+#[derive(PartialEq, Eq, Clone, Copy, Default)]
+pub enum FrictionModel {
+    #[default]
+    None,
+    /// Constant kinetic friction opposing the motion
+    Coulomb,
+    /// Breakaway friction decaying to the Coulomb level as the speed rises
+    Stribeck,
+}
+
+/// Dry friction coefficients of one joint, a force on the cart or a torque on a pivot
+#[derive(Clone, Copy, PartialEq)]
+pub struct DryFriction {
+    pub coulomb: f64,
+    pub breakaway: f64,
+    pub stribeck_velocity: f64,
+}
+
+impl DryFriction {
+    fn level(&self, model: FrictionModel, rate: f64) -> f64 {
+        match model {
+            FrictionModel::None => 0.,
+            FrictionModel::Coulomb => self.coulomb,
+            FrictionModel::Stribeck => {
+                let r = rate / self.stribeck_velocity.max(1e-9);
+                self.coulomb + (self.breakaway - self.coulomb) * libm::exp(-r * r)
+            }
+        }
+    }
+}
+
+/// Relaxation time of the residual rate of a stuck joint
+const STICK_TIME: f64 = 0.01;
+
+/// Dry friction on the cart wheels (or Furuta arm) and the pendulum pivot.
+/// The smooth form blends the sign of the rate over `band`; stick-slip holds
+/// a joint slower than `band` still until the force needed exceeds breakaway.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Friction {
+    pub model: FrictionModel,
+    pub stick_slip: bool,
+    pub band: f64,
+    pub cart: DryFriction,
+    pub pivot: DryFriction,
+}
+
+impl Default for Friction {
+    fn default() -> Self {
+        Friction {
+            model: FrictionModel::None,
+            stick_slip: false,
+            band: 0.01,
+            cart: DryFriction {
+                coulomb: 2.,
+                breakaway: 3.,
+                stribeck_velocity: 0.05,
+            },
+            pivot: DryFriction {
+                coulomb: 0.05,
+                breakaway: 0.08,
+                stribeck_velocity: 0.05,
+            },
+        }
+    }
+}
+
+impl Friction {
+    /// Friction on the cart and the pivot at rates `(v, w)`, where `acc` gives
+    /// the accelerations of both under extra forces on them, so a stuck joint
+    /// gets exactly the friction that holds it
+    pub fn forces(&self, v: f64, w: f64, acc: impl Fn([f64; 2]) -> [f64; 2]) -> [f64; 2] {
+        if self.model == FrictionModel::None {
+            return [0.; 2];
+        }
+        let joints = [(&self.cart, v), (&self.pivot, w)];
+        let sliding = joints.map(|(joint, rate)| {
+            if self.stick_slip {
+                -joint.level(self.model, rate) * rate.signum()
+            } else {
+                -joint.level(self.model, rate) * libm::tanh(rate / self.band)
+            }
+        });
+        let stuck = [0, 1].map(|i| self.stick_slip && joints[i].1.abs() < self.band);
+        if !stuck[0] && !stuck[1] {
+            return sliding;
+        }
+
+        // accelerations are affine in the forces, so probe their response
+        let mut f = [0, 1].map(|i| if stuck[i] { 0. } else { sliding[i] });
+        let a0 = acc(f);
+        let response = [0, 1].map(|j| {
+            let mut probe = f;
+            probe[j] += 1.;
+            let a = acc(probe);
+            [a[0] - a0[0], a[1] - a0[1]]
+        });
+        let target = [0, 1].map(|i| -joints[i].1 / STICK_TIME - a0[i]);
+        let needed = match stuck {
+            [true, true] => {
+                let (a, b, c, d) = (
+                    response[0][0],
+                    response[1][0],
+                    response[0][1],
+                    response[1][1],
+                );
+                let det = a * d - b * c;
+                [
+                    (d * target[0] - b * target[1]) / det,
+                    (a * target[1] - c * target[0]) / det,
+                ]
+            }
+            [true, false] => [target[0] / response[0][0], 0.],
+            _ => [0., target[1] / response[1][1]],
+        };
+        for i in 0..2 {
+            if stuck[i] {
+                let limit = joints[i].0.level(self.model, 0.);
+                f[i] = needed[i].clamp(-limit, limit);
+            }
+        }
+        f
+    }
+}
@@ -0,0 +1,36 @@
+/// Spring from the user's pointer to the first bob, pulling it towards
+/// `target`, a point in the plane of the pendulum measured from the pivot's
+/// height at `x = 0`. It is tuned by natural frequency so it feels the same
+/// whatever the pendulum's mass.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Hand {
+    pub target: Option<[f64; 2]>,
+    /// Natural frequency of the bob on the spring alone, rad/s
+    pub frequency: f64,
+    pub damping_ratio: f64,
+}
+
+impl Default for Hand {
+    fn default() -> Self {
+        Hand {
+            target: None,
+            frequency: 10.,
+            damping_ratio: 1.,
+        }
+    }
+}
+
+impl Hand {
+    /// Force on a bob of effective mass `mass` at `position` moving at `velocity`
+    pub fn force(&self, position: [f64; 2], velocity: [f64; 2], mass: f64) -> [f64; 2] {
+        let Some(target) = self.target else {
+            return [0.; 2];
+        };
+        let wn = self.frequency;
+        let spring = |i: usize| {
+            mass * (wn * wn * (target[i] - position[i])
+                - 2. * self.damping_ratio * wn * velocity[i])
+        };
+        [spring(0), spring(1)]
+    }
+}
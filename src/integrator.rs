@@ -0,0 +1,360 @@
+//! Fixed and adaptive one-step solvers for autonomous systems `y' = f(y)` over
+//! a state vector of any length, so every model shares the same schemes.
+//!
+//! The velocity-based schemes need to know which components are coordinates:
+//! the first `2 * pairs` entries of the state are interleaved `(position,
+//! velocity)` pairs, each position changing at the rate of its velocity, and
+//! any entries after them are advanced with explicit Euler.
+
+/// A one-step method advancing `y` by `dt` along the rates returned by `f`
+pub trait Integrator {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    );
+}
+
+/// Scheme selection, with the adaptive solver's state kept by the caller
+/// since its step size and statistics persist between calls
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegratorKind {
+    Euler,
+    SemiImplicitEuler,
+    VelocityVerlet,
+    /// Störmer-Verlet in kick-drift-kick form
+    Leapfrog,
+    #[default]
+    RungeKutta4,
+    DormandPrince,
+}
+
+impl IntegratorKind {
+    pub fn integrate<const N: usize>(
+        self,
+        adaptive: &mut DormandPrince,
+        pairs: usize,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        match self {
+            IntegratorKind::Euler => Euler.integrate(y, dt, f),
+            IntegratorKind::SemiImplicitEuler => SemiImplicitEuler { pairs }.integrate(y, dt, f),
+            IntegratorKind::VelocityVerlet => VelocityVerlet { pairs }.integrate(y, dt, f),
+            IntegratorKind::Leapfrog => Leapfrog { pairs }.integrate(y, dt, f),
+            IntegratorKind::RungeKutta4 => RungeKutta4.integrate(y, dt, f),
+            IntegratorKind::DormandPrince => adaptive.integrate(y, dt, f),
+        }
+    }
+}
+
+/// `y + dt k`
+fn after<const N: usize>(y: &[f64; N], k: &[f64; N], dt: f64) -> [f64; N] {
+    let mut next = *y;
+    for (n, k) in next.iter_mut().zip(k) {
+        *n += k * dt;
+    }
+    next
+}
+
+pub struct Euler;
+
+impl Integrator for Euler {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        *y = after(y, &f(y), dt);
+    }
+}
+
+pub struct RungeKutta4;
+
+impl Integrator for RungeKutta4 {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        let k1 = f(y);
+        let k2 = f(&after(y, &k1, 0.5 * dt));
+        let k3 = f(&after(y, &k2, 0.5 * dt));
+        let k4 = f(&after(y, &k3, dt));
+        for i in 0..N {
+            y[i] += (k1[i] + 2. * k2[i] + 2. * k3[i] + k4[i]) / 6. * dt;
+        }
+    }
+}
+
+/// Velocities first, then positions with the updated velocities
+pub struct SemiImplicitEuler {
+    pub pairs: usize,
+}
+
+impl Integrator for SemiImplicitEuler {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        let k = f(y);
+        for i in 0..N {
+            if i >= 2 * self.pairs || i % 2 == 1 {
+                y[i] += k[i] * dt;
+            }
+        }
+        for i in (0..2 * self.pairs).step_by(2) {
+            y[i] += y[i + 1] * dt;
+        }
+    }
+}
+
+pub struct VelocityVerlet {
+    pub pairs: usize,
+}
+
+impl Integrator for VelocityVerlet {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        let k0 = f(y);
+        let mut next = after(y, &k0, dt);
+        for i in (0..2 * self.pairs).step_by(2) {
+            next[i] = y[i] + (y[i + 1] + 0.5 * k0[i + 1] * dt) * dt;
+        }
+        let k1 = f(&next);
+        for i in (1..2 * self.pairs).step_by(2) {
+            next[i] = y[i] + 0.5 * (k0[i] + k1[i]) * dt;
+        }
+        *y = next;
+    }
+}
+
+/// Kick, drift, kick
+pub struct Leapfrog {
+    pub pairs: usize,
+}
+
+impl Integrator for Leapfrog {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        let k = f(y);
+        for i in 0..N {
+            if i >= 2 * self.pairs {
+                y[i] += k[i] * dt;
+            } else if i % 2 == 1 {
+                y[i] += 0.5 * k[i] * dt;
+            }
+        }
+        for i in (0..2 * self.pairs).step_by(2) {
+            y[i] += y[i + 1] * dt;
+        }
+        let k = f(y);
+        for i in (1..2 * self.pairs).step_by(2) {
+            y[i] += 0.5 * k[i] * dt;
+        }
+    }
+}
+
+/// Dormand-Prince 5(4) embedded pair with step size control, the internal
+/// step carrying over between calls so each tick only retries when needed
+#[derive(Clone, Copy, PartialEq)]
+pub struct DormandPrince {
+    pub atol: f64,
+    pub rtol: f64,
+    /// Step size the next attempt starts from
+    pub h: f64,
+    pub accepted: u64,
+    pub rejected: u64,
+    /// Set when the tolerance could not be met above the minimum step size
+    pub stalled: bool,
+    /// Set when even the minimum step size gave a solution that isn't finite,
+    /// the state then being left at the last finite one
+    pub failed: bool,
+}
+
+impl Default for DormandPrince {
+    fn default() -> Self {
+        DormandPrince {
+            atol: 1e-6,
+            rtol: 1e-6,
+            h: 1e-3,
+            accepted: 0,
+            rejected: 0,
+            stalled: false,
+            failed: false,
+        }
+    }
+}
+
+const MIN_STEP: f64 = 1e-9;
+
+const A: [[f64; 6]; 7] = [
+    [0., 0., 0., 0., 0., 0.],
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [
+        19372. / 6561.,
+        -25360. / 2187.,
+        64448. / 6561.,
+        -212. / 729.,
+        0.,
+        0.,
+    ],
+    [
+        9017. / 3168.,
+        -355. / 33.,
+        46732. / 5247.,
+        49. / 176.,
+        -5103. / 18656.,
+        0.,
+    ],
+    [
+        35. / 384.,
+        0.,
+        500. / 1113.,
+        125. / 192.,
+        -2187. / 6784.,
+        11. / 84.,
+    ],
+];
+/// Fifth order weights minus the embedded fourth order ones
+const E: [f64; 7] = [
+    35. / 384. - 5179. / 57600.,
+    0.,
+    500. / 1113. - 7571. / 16695.,
+    125. / 192. - 393. / 640.,
+    -2187. / 6784. + 92097. / 339200.,
+    11. / 84. - 187. / 2100.,
+    -1. / 40.,
+];
+
+impl DormandPrince {
+    pub fn reset(&mut self) {
+        *self = DormandPrince {
+            atol: self.atol,
+            rtol: self.rtol,
+            ..Default::default()
+        };
+    }
+}
+
+impl Integrator for DormandPrince {
+    fn integrate<const N: usize>(
+        &mut self,
+        y: &mut [f64; N],
+        dt: f64,
+        f: impl Fn(&[f64; N]) -> [f64; N],
+    ) {
+        let mut t = 0.;
+        while t < dt {
+            let last = self.h >= dt - t;
+            let h = if last { dt - t } else { self.h };
+            let mut k = [[0.; N]; 7];
+            k[0] = f(y);
+            for s in 1..7 {
+                let mut ys = *y;
+                for (i, yi) in ys.iter_mut().enumerate() {
+                    *yi += h * (0..s).map(|j| A[s][j] * k[j][i]).sum::<f64>();
+                }
+                k[s] = f(&ys);
+            }
+            // the last stage is evaluated at the fifth order solution
+            let mut next = *y;
+            for (i, n) in next.iter_mut().enumerate() {
+                *n += h * (0..6).map(|j| A[6][j] * k[j][i]).sum::<f64>();
+            }
+            let mut err: f64 = 0.;
+            for i in 0..N {
+                let e = h * (0..7).map(|j| E[j] * k[j][i]).sum::<f64>();
+                let sc = self.atol + self.rtol * y[i].abs().max(next[i].abs());
+                let ratio = (e / sc).abs();
+                // `max` would pass over a NaN, so a blown-up step is caught here
+                if !ratio.is_finite() || !next[i].is_finite() {
+                    err = f64::INFINITY;
+                    break;
+                }
+                err = err.max(ratio);
+            }
+
+            let factor = if err == 0. {
+                5.
+            } else {
+                (0.9 * libm::pow(err, -0.2)).clamp(0.2, 5.)
+            };
+            if err.is_infinite() && h <= MIN_STEP {
+                self.failed = true;
+                self.stalled = true;
+                self.rejected += 1;
+                return;
+            }
+            if err <= 1. || h <= MIN_STEP {
+                self.stalled = err > 1.;
+                self.failed = false;
+                *y = next;
+                t = if last { dt } else { t + h };
+                self.accepted += 1;
+                // a step cut short by the end of the interval says little about the next one
+                if !last {
+                    self.h = h * factor;
+                }
+            } else {
+                self.rejected += 1;
+                self.h = (h * factor).max(MIN_STEP);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_scheme_handles_pairs_and_extra_states() {
+        // an oscillator with a decaying first order state after it
+        let f = |y: &[f64; 3]| [y[1], -y[0], -y[2]];
+        let kinds = [
+            IntegratorKind::Euler,
+            IntegratorKind::SemiImplicitEuler,
+            IntegratorKind::VelocityVerlet,
+            IntegratorKind::Leapfrog,
+            IntegratorKind::RungeKutta4,
+            IntegratorKind::DormandPrince,
+        ];
+        for kind in kinds {
+            let mut adaptive = DormandPrince::default();
+            let mut y = [1., 0., 1.];
+            for _ in 0..1000 {
+                kind.integrate(&mut adaptive, 1, &mut y, 1e-3, f);
+            }
+            let (c, s, e) = (libm::cos(1.), libm::sin(1.), libm::exp(-1.));
+            let err = (y[0] - c).abs() + (y[1] + s).abs() + (y[2] - e).abs();
+            assert!(err < 2e-3, "error {err} too large");
+        }
+    }
+
+    #[test]
+    fn adaptive_solver_stops_at_a_blow_up() {
+        // y' = y² from 1 reaches infinity at t = 1
+        let mut adaptive = DormandPrince::default();
+        let mut y = [1.];
+        adaptive.integrate(&mut y, 2., |y| [y[0] * y[0]]);
+        assert!(adaptive.failed && adaptive.stalled && adaptive.rejected > 0);
+        assert!(y[0].is_finite() && y[0] > 1e6, "{}", y[0]);
+    }
+}
@@ -0,0 +1,16 @@
+pub mod actuator;
+pub mod cart;
+pub mod controller;
+pub mod disturbance;
+pub mod friction;
+pub mod hand;
+pub mod integrator;
+pub mod linalg;
+pub mod lqr;
+pub mod motor;
+pub mod observer;
+pub mod params;
+pub mod sensor;
+pub mod state;
+pub mod swingup;
+pub mod track;
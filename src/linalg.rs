@@ -0,0 +1,148 @@
+//! Small dense matrix helpers on fixed size arrays, enough for the few
+//! linear-quadratic problems the controllers and observers need to solve.
+
+pub type Matrix<const R: usize, const C: usize> = [[f64; C]; R];
+
+pub fn zeros<const R: usize, const C: usize>() -> Matrix<R, C> {
+    [[0.; C]; R]
+}
+
+pub fn identity<const N: usize>() -> Matrix<N, N> {
+    let mut m = zeros();
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = 1.;
+    }
+    m
+}
+
+pub fn diag<const N: usize>(d: [f64; N]) -> Matrix<N, N> {
+    let mut m = zeros();
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = d[i];
+    }
+    m
+}
+
+pub fn mul<const R: usize, const K: usize, const C: usize>(
+    a: &Matrix<R, K>,
+    b: &Matrix<K, C>,
+) -> Matrix<R, C> {
+    let mut m = zeros();
+    for i in 0..R {
+        for j in 0..C {
+            m[i][j] = (0..K).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+pub fn mul_vec<const R: usize, const C: usize>(a: &Matrix<R, C>, v: &[f64; C]) -> [f64; R] {
+    let mut out = [0.; R];
+    for (o, row) in out.iter_mut().zip(a) {
+        *o = row.iter().zip(v).map(|(a, b)| a * b).sum();
+    }
+    out
+}
+
+pub fn transpose<const R: usize, const C: usize>(a: &Matrix<R, C>) -> Matrix<C, R> {
+    let mut m = zeros();
+    for i in 0..R {
+        for j in 0..C {
+            m[j][i] = a[i][j];
+        }
+    }
+    m
+}
+
+pub fn add<const R: usize, const C: usize>(a: &Matrix<R, C>, b: &Matrix<R, C>) -> Matrix<R, C> {
+    let mut m = *a;
+    for i in 0..R {
+        for j in 0..C {
+            m[i][j] += b[i][j];
+        }
+    }
+    m
+}
+
+pub fn scale<const R: usize, const C: usize>(a: &Matrix<R, C>, k: f64) -> Matrix<R, C> {
+    a.map(|row| row.map(|x| x * k))
+}
+
+/// Gauss-Jordan elimination with partial pivoting, `None` if singular
+pub fn inverse<const N: usize>(a: &Matrix<N, N>) -> Option<Matrix<N, N>> {
+    let mut a = *a;
+    let mut inv = identity::<N>();
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+        let p = a[col][col];
+        for j in 0..N {
+            a[col][j] /= p;
+            inv[col][j] /= p;
+        }
+        for i in 0..N {
+            if i != col {
+                let f = a[i][col];
+                for j in 0..N {
+                    a[i][j] -= f * a[col][j];
+                    inv[i][j] -= f * inv[col][j];
+                }
+            }
+        }
+    }
+    Some(inv)
+}
+
+/// Stabilizing solution of the continuous-time algebraic Riccati equation
+/// `A'P + PA - PBR⁻¹B'P + Q = 0`, found through the matrix sign function of
+/// the Hamiltonian. `N2` must be `2 * N`.
+pub fn care<const N: usize, const M: usize, const N2: usize>(
+    a: &Matrix<N, N>,
+    b: &Matrix<N, M>,
+    q: &Matrix<N, N>,
+    r: &Matrix<M, M>,
+) -> Option<Matrix<N, N>> {
+    assert_eq!(N2, 2 * N);
+    let s = mul(&mul(b, &inverse(r)?), &transpose(b));
+    let mut z: Matrix<N2, N2> = zeros();
+    for i in 0..N {
+        for j in 0..N {
+            z[i][j] = a[i][j];
+            z[i][j + N] = -s[i][j];
+            z[i + N][j] = -q[i][j];
+            z[i + N][j + N] = -a[j][i];
+        }
+    }
+
+    let norm = |m: &Matrix<N2, N2>| m.iter().flatten().map(|x| x * x).sum::<f64>().sqrt();
+    for _ in 0..100 {
+        let inv = inverse(&z)?;
+        let c = (norm(&inv) / norm(&z)).sqrt();
+        let next = scale(&add(&scale(&z, c), &scale(&inv, 1. / c)), 0.5);
+        let change = norm(&add(&next, &scale(&z, -1.)));
+        z = next;
+        if change < 1e-10 * norm(&z) {
+            break;
+        }
+    }
+
+    // the stable subspace [I; P] satisfies sign(H) [I; P] = -[I; P]
+    let mut lhs: Matrix<N2, N> = zeros();
+    let mut rhs: Matrix<N2, N> = zeros();
+    for i in 0..N {
+        for j in 0..N {
+            lhs[i][j] = z[i][j + N];
+            lhs[i + N][j] = z[i + N][j + N] + if i == j { 1. } else { 0. };
+            rhs[i][j] = -z[i][j] - if i == j { 1. } else { 0. };
+            rhs[i + N][j] = -z[i + N][j];
+        }
+    }
+    let lt = transpose(&lhs);
+    let p = mul(&inverse(&mul(&lt, &lhs))?, &mul(&lt, &rhs));
+    let p = scale(&add(&p, &transpose(&p)), 0.5);
+    p.iter().flatten().all(|x| x.is_finite()).then_some(p)
+}
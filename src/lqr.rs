@@ -0,0 +1,76 @@
+use crate::{
+    controller::Controller,
+    linalg::{care, diag, mul, transpose, Matrix},
+    state::{wrap_angle, State},
+};
+
+/// Full state feedback `F = -K (x - x_ref, v, th - setpoint, w, th2 - setpoint, w2)'`
+/// with the gain from the continuous-time LQR problem on the plant linearized
+/// about upright. The single pendulum only uses the first four entries.
+#[derive(Clone, PartialEq)]
+pub struct Lqr {
+    pub x_ref: f64,
+    pub q: [f64; 6],
+    pub r: f64,
+    pub k: [f64; 6],
+    pub solved: bool,
+    solved_for: Option<(Vec<f64>, [f64; 6], f64)>,
+}
+
+impl Default for Lqr {
+    fn default() -> Self {
+        Lqr {
+            x_ref: 0.,
+            q: [100., 10., 100., 1., 100., 1.],
+            r: 0.01,
+            k: [0.; 6],
+            solved: false,
+            solved_for: None,
+        }
+    }
+}
+
+impl Lqr {
+    /// Recomputes the gain for the first `N` states if the linearization or
+    /// the weights changed. `N2` must be `2 * N`.
+    pub fn update_gain<const N: usize, const N2: usize>(
+        &mut self,
+        a: Matrix<N, N>,
+        b: Matrix<N, 1>,
+    ) {
+        let linearization = a
+            .iter()
+            .flatten()
+            .chain(b.iter().flatten())
+            .copied()
+            .collect();
+        let key = Some((linearization, self.q, self.r));
+        if self.solved_for == key {
+            return;
+        }
+        self.solved_for = key;
+        let q = std::array::from_fn(|i| self.q[i]);
+        let p = care::<N, 1, N2>(&a, &b, &diag(q), &[[self.r]]);
+        self.solved = p.is_some();
+        self.k = [0.; 6];
+        if let Some(p) = p {
+            for (k, x) in self.k.iter_mut().zip(mul(&transpose(&b), &p)[0]) {
+                *k = x / self.r;
+            }
+        }
+    }
+}
+
+impl Controller for Lqr {
+    fn control(&mut self, state: &State, _t: f64, setpoint: f64, _dt: f64) -> f64 {
+        let x = [
+            state.x - self.x_ref,
+            state.v,
+            wrap_angle(state.th - setpoint),
+            state.w,
+            wrap_angle(state.th2 - setpoint),
+            state.w2,
+        ];
+        -self.k.iter().zip(x).map(|(k, x)| k * x).sum::<f64>()
+    }
+}
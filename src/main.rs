@@ -1,14 +1,17 @@
-use crate::{theme::setup_theme, ui::Graph};
-use cart::Cart;
+use crate::{
+    theme::setup_theme,
+    ui::{Graph, Timeline},
+    view::View,
+};
 use egui::{pos2, Color32};
 use egui_macroquad::egui;
 use macroquad::prelude::*;
+use pid_balancer::{cart::Cart, state::wrap_angle};
 use ui::{draw_blue_grid, draw_speedometer, draw_ui, draw_vingette};
 mod camera;
-mod cart;
-mod state;
 mod theme;
 mod ui;
+mod view;
 
 fn window_conf() -> Conf {
     Conf {
@@ -25,6 +28,8 @@ async fn main() {
     let grid = 0.15;
     let w_init = 1280.;
     let mut cart = Cart::default();
+    let mut view = View::default();
+    let mut timeline = Timeline::default();
     let vingette = Texture2D::from_file_with_format(include_bytes!("../vingette.png"), None);
     let font = load_ttf_font_from_bytes(include_bytes!("../Ubuntu-Regular.ttf")).unwrap();
     setup_theme();
@@ -35,32 +40,101 @@ async fn main() {
         None,
     );
     let mut forceplt1 = Graph::new(
-        &["PID", "Integral", "Derivative", "Error"],
+        &[
+            "PID",
+            "Integral",
+            "Derivative",
+            "Error",
+            "True θ",
+            "Measured θ",
+        ],
         pos2((0.5 + 0.5 * grid) * w_init, 0.),
         egui::vec2(1.5, 1.) * grid * w_init,
-        Some([Color32::WHITE, Color32::LIGHT_GREEN, Color32::LIGHT_RED].to_vec()),
+        Some(
+            [
+                Color32::WHITE,
+                Color32::LIGHT_GREEN,
+                Color32::LIGHT_RED,
+                Color32::GOLD,
+                Color32::from_rgb(255, 128, 255),
+            ]
+            .to_vec(),
+        ),
+    );
+    let mut motorplt = Graph::new(
+        &["Motor", "Voltage", "Current"],
+        pos2((0.5 - 2. * grid) * w_init, 0.),
+        egui::vec2(1.5, 1.) * grid * w_init,
+        Some([Color32::LIGHT_YELLOW, Color32::LIGHT_BLUE].to_vec()),
+    );
+    let mut observerplt = Graph::new(
+        &["Observer", "True θ", "Estimated θ", "True ω", "Estimated ω"],
+        pos2((0.5 + 0.5 * grid) * w_init, 0.),
+        egui::vec2(1.5, 1.) * grid * w_init,
+        Some(
+            [
+                Color32::WHITE,
+                Color32::from_rgb(255, 128, 255),
+                Color32::LIGHT_GREEN,
+                Color32::GOLD,
+            ]
+            .to_vec(),
+        ),
     );
     next_frame().await;
     let back_color = Color::new(0.00, 0.43, 0.95, 1.00);
 
     loop {
-        set_camera(&Camera2D {
+        let camera = Camera2D {
             zoom: vec2(1., screen_width() / screen_height()),
             ..Default::default()
-        });
+        };
+        set_camera(&camera);
         if is_key_pressed(KeyCode::Q) || is_key_pressed(KeyCode::Escape) {
             break;
         }
         if get_time() > 0. {
+            cart.push = if is_key_down(KeyCode::Left) {
+                -1.
+            } else if is_key_down(KeyCode::Right) {
+                1.
+            } else {
+                0.
+            };
+            let mouse = camera.screen_to_world(mouse_position().into());
+            view.drag_target(&mut cart, mouse, 3. * grid);
+            view.drag_bob(&mut cart, mouse, 3. * grid);
             cart.update(get_frame_time() as f64);
+            view.update(&cart, get_frame_time() as f64);
         }
-        forceplt.update([cart.F].to_vec());
-        forceplt1.update([cart.int, -cart.state.w, cart.error].to_vec());
+        forceplt.update(cart.time, [cart.force()].to_vec());
+        motorplt.update(cart.time, [cart.V, cart.state.i].to_vec());
+        forceplt1.update(
+            cart.time,
+            [
+                cart.pid.int,
+                cart.pid.derivative,
+                cart.pid.error,
+                wrap_angle(cart.state.th - cart.setpoint),
+                wrap_angle(cart.measured.th - cart.setpoint),
+            ]
+            .to_vec(),
+        );
+        observerplt.update(
+            cart.time,
+            [
+                wrap_angle(cart.state.th - cart.setpoint),
+                wrap_angle(cart.observer.estimate.th - cart.setpoint),
+                cart.state.w,
+                cart.observer.estimate.w,
+            ]
+            .to_vec(),
+        );
 
         clear_background(back_color);
         draw_blue_grid(grid, SKYBLUE, 0.001, 3, 0.003);
 
-        cart.display(back_color, WHITE, 0.006, 6. * grid, 3. * grid);
+        view.draw_cart(&cart, back_color, WHITE, 0.006, 6. * grid, 3. * grid);
         draw_speedometer(
             &format!(
                 "Angular Velocity ({}) {:.2}",
@@ -99,7 +173,17 @@ async fn main() {
             14.,
             true,
         );
-        draw_ui(w_init, grid, &mut cart, &mut forceplt, &mut forceplt1);
+        draw_ui(
+            w_init,
+            grid,
+            &mut cart,
+            &mut view,
+            &mut forceplt,
+            &mut forceplt1,
+            &mut motorplt,
+            &mut observerplt,
+            &mut timeline,
+        );
         draw_vingette(vingette);
         next_frame().await;
     }
@@ -0,0 +1,70 @@
+#![allow(non_snake_case)]
+
+/// Permanent magnet DC motor driving the wheels, or the Furuta arm, through a
+/// gearbox. The armature current is part of the state and the controller
+/// output becomes the terminal voltage; an armature that settles within a
+/// physics tick is held at its settled current instead.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Motor {
+    pub enable: bool,
+    /// Armature resistance (Ω) and inductance (H)
+    pub Ra: f64,
+    pub La: f64,
+    /// Torque constant (N·m/A) and back-EMF constant (V·s/rad)
+    pub Kt: f64,
+    pub Ke: f64,
+    /// Motor turns per wheel turn
+    pub gear: f64,
+    /// Supply voltage the command is clamped to
+    pub v_max: f64,
+}
+
+impl Default for Motor {
+    fn default() -> Self {
+        Motor {
+            enable: false,
+            Ra: 1.,
+            La: 0.01,
+            Kt: 0.05,
+            Ke: 0.05,
+            gear: 20.,
+            v_max: 24.,
+        }
+    }
+}
+
+impl Motor {
+    // `radius` turns the shaft torque into a force at the wheel rim, 1 when the
+    // driven coordinate is itself an angle
+
+    pub fn force(&self, current: f64, radius: f64) -> f64 {
+        self.gear * self.Kt * current / radius
+    }
+
+    /// Rate of change of the current at `voltage`, the driven coordinate moving at `speed`
+    pub fn current_rate(&self, current: f64, voltage: f64, speed: f64, radius: f64) -> f64 {
+        (voltage - self.Ra * current - self.Ke * self.gear * speed / radius) / self.La
+    }
+
+    /// Whether the current settles within a step of `dt`, which the explicit
+    /// schemes can't follow without going unstable
+    pub fn settles_within(&self, dt: f64) -> bool {
+        self.La < self.Ra * dt
+    }
+
+    /// Current the armature settles to at `voltage`
+    pub fn steady_current(&self, voltage: f64, speed: f64, radius: f64) -> f64 {
+        (voltage - self.Ke * self.gear * speed / radius) / self.Ra
+    }
+
+    /// Force once the current has settled, the electrical time constant being
+    /// much shorter than the mechanical ones
+    pub fn steady_force(&self, voltage: f64, speed: f64, radius: f64) -> f64 {
+        self.force(self.steady_current(voltage, speed, radius), radius)
+    }
+
+    /// Voltage that settles to `force` at `speed`
+    pub fn voltage_for(&self, force: f64, speed: f64, radius: f64) -> f64 {
+        self.Ra * force * radius / (self.gear * self.Kt) + self.Ke * self.gear * speed / radius
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    integrator::{Integrator, RungeKutta4},
+    linalg::{add, care, diag, identity, inverse, mul, mul_vec, scale, transpose, zeros, Matrix},
+    state::{wrap_angle, State},
+};
+
+/// What the controllers are fed
+#[derive(PartialEq, Eq, Clone, Copy, Default)]
+pub enum Estimator {
+    /// The sensor readings, rates by differencing
+    #[default]
+    Measured,
+    Luenberger,
+    /// Discrete extended Kalman filter
+    Kalman,
+}
+
+/// The measured `(x, th, th2)` out of `(x, v, th, w, th2, w2)`
+const C: Matrix<3, 6> = [
+    [1., 0., 0., 0., 0., 0.],
+    [0., 0., 1., 0., 0., 0.],
+    [0., 0., 0., 0., 1., 0.],
+];
+
+/// Reconstructs the rates from the measured positions and angles by running the
+/// plant model alongside the real one and correcting it with the output error
+#[derive(Clone, PartialEq)]
+pub struct Observer {
+    pub source: Estimator,
+    /// State to output weight ratio the Luenberger gain is designed for,
+    /// higher trusting the sensors over the model
+    pub weight: f64,
+    /// Standard deviation of the unmodelled accelerations the Kalman filter allows for
+    pub process_noise: f64,
+    /// Luenberger gain from the output error to the state rates
+    pub gain: Matrix<6, 3>,
+    pub covariance: Matrix<6, 6>,
+    pub estimate: State,
+    gain_for: Option<Vec<f64>>,
+    started: bool,
+}
+
+impl Default for Observer {
+    fn default() -> Self {
+        Observer {
+            source: Estimator::Measured,
+            weight: 1000.,
+            process_noise: 1.,
+            gain: zeros(),
+            covariance: zeros(),
+            estimate: State::default(),
+            gain_for: None,
+            started: false,
+        }
+    }
+}
+
+fn innovation(y: &[f64; 3], x: &[f64; 6]) -> [f64; 3] {
+    [
+        y[0] - x[0],
+        wrap_angle(y[1] - x[2]),
+        wrap_angle(y[2] - x[4]),
+    ]
+}
+
+impl Observer {
+    /// Designs the Luenberger gain as the dual of an LQR problem on the `N`
+    /// state linearization `a` measured through `M` outputs, if it or the weight
+    /// changed. `N2` must be `2 * N`.
+    pub fn update_gain<const N: usize, const M: usize, const N2: usize>(
+        &mut self,
+        a: Matrix<N, N>,
+    ) {
+        let key = Some(a.iter().flatten().chain([&self.weight]).copied().collect());
+        if self.gain_for == key {
+            return;
+        }
+        self.gain_for = key;
+        let c: Matrix<M, N> = std::array::from_fn(|i| std::array::from_fn(|j| C[i][j]));
+        let q = scale(&identity(), self.weight);
+        let p = care::<N, M, N2>(&transpose(&a), &transpose(&c), &q, &identity());
+        self.gain = zeros();
+        if let Some(p) = p {
+            let l = mul(&p, &transpose(&c));
+            for (row, l) in self.gain.iter_mut().zip(l) {
+                row[..M].copy_from_slice(&l);
+            }
+        }
+    }
+
+    /// Advances the estimate by `dt` along `rates` and corrects it with the
+    /// reading `measured`, whose entries have the given `variances`
+    pub fn update(
+        &mut self,
+        measured: &State,
+        dt: f64,
+        variances: [f64; 3],
+        rates: impl Fn(&[f64; 6]) -> [f64; 6],
+    ) -> State {
+        if self.source == Estimator::Measured {
+            return *measured;
+        }
+        if !self.started {
+            self.started = true;
+            self.estimate = State {
+                v: 0.,
+                w: 0.,
+                w2: 0.,
+                ..*measured
+            };
+            self.covariance = diag([1e-2, 1., 1e-2, 1., 1e-2, 1.]);
+            return self.estimate;
+        }
+        let y = [measured.x, measured.th, measured.th2];
+        let [x, v, th, w, th2, w2, _] = self.estimate.to_vector();
+        let mut x = [x, v, th, w, th2, w2];
+
+        let jacobian = (self.source == Estimator::Kalman).then(|| {
+            let eps = 1e-6;
+            let mut a: Matrix<6, 6> = zeros();
+            for j in 0..6 {
+                let (mut hi, mut lo) = (x, x);
+                hi[j] += eps;
+                lo[j] -= eps;
+                let (hi, lo) = (rates(&hi), rates(&lo));
+                for i in 0..6 {
+                    a[i][j] = (hi[i] - lo[i]) / (2. * eps);
+                }
+            }
+            a
+        });
+        RungeKutta4.integrate(&mut x, dt, &rates);
+
+        let e = innovation(&y, &x);
+        if let Some(a) = jacobian {
+            let f = add(&identity(), &scale(&a, dt));
+            let mut q: Matrix<6, 6> = zeros();
+            for i in [1, 3, 5] {
+                q[i][i] = self.process_noise * self.process_noise * dt;
+            }
+            let p = add(&mul(&mul(&f, &self.covariance), &transpose(&f)), &q);
+            let pct = mul(&p, &transpose(&C));
+            let s = add(&mul(&C, &pct), &diag(variances.map(|r| r.max(1e-12))));
+            let k = mul(&pct, &inverse(&s).unwrap_or_default());
+            for (xi, ki) in x.iter_mut().zip(mul_vec(&k, &e)) {
+                *xi += ki;
+            }
+            self.covariance = mul(&add(&identity(), &scale(&mul(&k, &C), -1.)), &p);
+        } else {
+            for (xi, li) in x.iter_mut().zip(mul_vec(&self.gain, &e)) {
+                *xi += li * dt;
+            }
+        }
+        let [x, v, th, w, th2, w2] = x;
+        self.estimate = State::from_vector([x, v, th, w, th2, w2, measured.i]);
+        self.estimate
+    }
+
+    pub fn reset(&mut self) {
+        self.started = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_recover_the_unmeasured_rates() {
+        // x'' = -x read through x alone, started at rest while it moves
+        let rates = |y: &[f64; 6]| [y[1], -y[0], 0., 0., 0., 0.];
+        for source in [Estimator::Luenberger, Estimator::Kalman] {
+            let mut observer = Observer {
+                source,
+                ..Default::default()
+            };
+            observer.update_gain::<2, 1, 4>([[0., 1.], [-1., 0.]]);
+            let dt = 0.01;
+            for n in 0..1000 {
+                let t = n as f64 * dt;
+                let measured = State::from(libm::sin(t), 0., 0., 0.);
+                observer.update(&measured, dt, [1e-6; 3], rates);
+            }
+            let v = libm::cos(1000. * dt);
+            assert!(
+                (observer.estimate.v - v).abs() < 1e-2,
+                "estimator {}: {} for {v}",
+                source as u8,
+                observer.estimate.v
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+#![allow(non_snake_case)]
+
+/// Mechanism mounted on the cart
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Plant {
+    #[default]
+    Single,
+    /// A second link hinged at the bob of the first
+    Double,
+    /// Rotary pendulum on a driven horizontal arm, the arm angle and rate taking
+    /// the place of the cart position and velocity and the force becoming a torque
+    Furuta,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct CartParams {
+    pub M: f64,
+    pub m: f64,
+    pub ml: f64,
+    pub mw: f64,
+    pub l: f64,
+    pub R: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub g: f64,
+    /// Bob mass, rod mass, length and joint drag of the second link
+    pub m_2: f64,
+    pub ml_2: f64,
+    pub l_2: f64,
+    pub b3: f64,
+    /// Length and rotational inertia of the Furuta arm
+    pub l_arm: f64,
+    pub J_arm: f64,
+}
+
+impl Default for CartParams {
+    fn default() -> Self {
+        CartParams {
+            M: 5.,
+            m: 0.5,
+            ml: 1.,
+            mw: 1.,
+            l: 1.,
+            R: 0.1,
+            b1: 0.01,
+            b2: 0.005,
+            g: 9.80665,
+            m_2: 0.5,
+            ml_2: 0.5,
+            l_2: 1.,
+            b3: 0.005,
+            l_arm: 1.,
+            J_arm: 0.5,
+        }
+    }
+}
+
+impl CartParams {
+    /// Smallest cart mass, and smallest mass of each link, that the setters
+    /// allow, keeping the mass matrix invertible
+    pub const MIN_MASS: f64 = 1e-3;
+
+    pub fn set_cart_mass(&mut self, M: f64) {
+        self.M = M.max(Self::MIN_MASS);
+    }
+
+    pub fn set_wheel_mass(&mut self, mw: f64) {
+        self.mw = mw.max(0.);
+    }
+
+    // the bob or the rod of a link may be massless, but not both
+
+    pub fn set_bob_mass(&mut self, m: f64) {
+        self.m = m.max(0.).max(Self::MIN_MASS - self.ml);
+    }
+
+    pub fn set_rod_mass(&mut self, ml: f64) {
+        self.ml = ml.max(0.).max(Self::MIN_MASS - self.m);
+    }
+
+    pub fn set_second_bob_mass(&mut self, m_2: f64) {
+        self.m_2 = m_2.max(0.).max(Self::MIN_MASS - self.ml_2);
+    }
+
+    pub fn set_second_rod_mass(&mut self, ml_2: f64) {
+        self.ml_2 = ml_2.max(0.).max(Self::MIN_MASS - self.m_2);
+    }
+
+    // derived masses are computed on demand so edits always reach the dynamics
+
+    /// Total translating mass, wheels counted with their rolling inertia
+    pub fn m1(&self) -> f64 {
+        self.m + self.M + self.ml + 3. * self.mw
+    }
+
+    /// Rotational inertia of the pendulum about the pivot, divided by l²
+    pub fn m2(&self) -> f64 {
+        self.m + self.ml / 3.
+    }
+
+    /// First moment of the pendulum about the pivot, divided by l
+    pub fn m3(&self) -> f64 {
+        self.m + self.ml / 2.
+    }
+
+    /// Mass of the second link
+    pub fn m4(&self) -> f64 {
+        self.m_2 + self.ml_2
+    }
+
+    /// Rotational inertia of the second link about its joint, divided by l_2²
+    pub fn m5(&self) -> f64 {
+        self.m_2 + self.ml_2 / 3.
+    }
+
+    /// First moment of the second link about its joint, divided by l_2
+    pub fn m6(&self) -> f64 {
+        self.m_2 + self.ml_2 / 2.
+    }
+
+    /// Inertia of the Furuta arm with the pendulum's mass at its tip
+    pub fn arm_inertia(&self) -> f64 {
+        self.J_arm + (self.m + self.ml) * self.l_arm * self.l_arm
+    }
+
+    /// Lever arm of the drive, the wheel radius or 1 for the angle-driven arm
+    pub fn drive_radius(&self, plant: Plant) -> f64 {
+        match plant {
+            Plant::Furuta => 1.,
+            _ => self.R,
+        }
+    }
+}
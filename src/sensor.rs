@@ -0,0 +1,173 @@
+use std::f64::consts::PI;
+
+use crate::state::{wrap_angle, State};
+
+/// SplitMix64, small and seedable so noisy runs can be replayed exactly
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `(0, 1]`
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, by Box-Muller
+    pub fn gaussian(&mut self) -> f64 {
+        let (u1, u2) = (self.uniform(), self.uniform());
+        libm::sqrt(-2. * libm::log(u1)) * libm::cos(2. * PI * u2)
+    }
+}
+
+/// Analogue front end of one measurement
+#[derive(Clone, Copy, PartialEq)]
+pub struct Channel {
+    /// Standard deviation of the additive Gaussian noise
+    pub noise: f64,
+    pub bias: f64,
+    /// ADC step, 0 for an ideal converter
+    pub quantization: f64,
+}
+
+impl Channel {
+    pub fn measure(&self, value: f64, rng: &mut Rng) -> f64 {
+        let reading = value + self.bias + self.noise * rng.gaussian();
+        if self.quantization > 0. {
+            (reading / self.quantization).round() * self.quantization
+        } else {
+            reading
+        }
+    }
+}
+
+/// Position and angle sensing for the controllers. Only `x`, `th` and `th2`
+/// are measured; the rates are backward differences of the measurements.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Sensors {
+    pub enable: bool,
+    pub seed: u64,
+    pub position: Channel,
+    pub angle: Channel,
+    /// Encoder counts per revolution on the wheel and the pivots, 0 for none
+    pub cpr: u32,
+    rng: Rng,
+    last: Option<State>,
+}
+
+impl Default for Sensors {
+    fn default() -> Self {
+        Sensors {
+            enable: false,
+            seed: 1,
+            position: Channel {
+                noise: 1e-3,
+                bias: 0.,
+                quantization: 0.,
+            },
+            angle: Channel {
+                noise: 1e-3,
+                bias: 0.,
+                quantization: 0.,
+            },
+            cpr: 0,
+            rng: Rng::new(1),
+            last: None,
+        }
+    }
+}
+
+impl Sensors {
+    /// Reading of `state` taken `dt` after the previous one, with the wheel or
+    /// arm turning `radius` times slower than the position changes
+    pub fn measure(&mut self, state: &State, radius: f64, dt: f64) -> State {
+        let count = |value: f64, per_turn: f64| {
+            if self.cpr == 0 {
+                return value;
+            }
+            let resolution = per_turn / self.cpr as f64;
+            (value / resolution).floor() * resolution
+        };
+        let x = count(state.x, 2. * PI * radius);
+        let (th, th2) = (count(state.th, 2. * PI), count(state.th2, 2. * PI));
+        let x = self.position.measure(x, &mut self.rng);
+        let th = self.angle.measure(th, &mut self.rng);
+        let th2 = self.angle.measure(th2, &mut self.rng);
+
+        let mut measured = *state;
+        measured.x = x;
+        measured.th = th;
+        measured.th2 = th2;
+        measured.normalize();
+        (measured.v, measured.w, measured.w2) = match self.last.filter(|_| dt > 0.) {
+            Some(last) => (
+                (measured.x - last.x) / dt,
+                wrap_angle(measured.th - last.th) / dt,
+                wrap_angle(measured.th2 - last.th2) / dt,
+            ),
+            None => (0., 0., 0.),
+        };
+        self.last = Some(measured);
+        measured
+    }
+
+    /// Variance of each reading of `(x, th, th2)`, counting rounding as a uniform error
+    pub fn variances(&self, radius: f64) -> [f64; 3] {
+        let rounding = |step: f64| step * step / 12.;
+        let encoder = |per_turn: f64| match self.cpr {
+            0 => 0.,
+            cpr => rounding(per_turn / cpr as f64),
+        };
+        let channel = |c: &Channel| c.noise * c.noise + rounding(c.quantization);
+        let angle = channel(&self.angle) + encoder(2. * PI);
+        [
+            channel(&self.position) + encoder(2. * PI * radius),
+            angle,
+            angle,
+        ]
+    }
+
+    /// Restarts the noise from `seed`
+    pub fn reseed(&mut self) {
+        self.rng = Rng::new(self.seed);
+    }
+
+    pub fn reset(&mut self) {
+        self.reseed();
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_noise_has_the_set_bias_and_spread() {
+        let channel = Channel {
+            noise: 0.01,
+            bias: 0.1,
+            quantization: 0.,
+        };
+        let mut rng = Rng::new(7);
+        let readings: Vec<_> = (0..20000).map(|_| channel.measure(1., &mut rng)).collect();
+        let mean = readings.iter().sum::<f64>() / readings.len() as f64;
+        let var = readings.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / readings.len() as f64;
+        assert!((mean - 1.1).abs() < 1e-3);
+        assert!((var.sqrt() - 0.01).abs() < 1e-3);
+
+        let mut again = Rng::new(7);
+        assert_eq!(channel.measure(1., &mut again), readings[0]);
+    }
+}
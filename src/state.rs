@@ -7,6 +7,11 @@ pub struct State {
     pub v: f64,
     pub w: f64,
     pub th: f64,
+    /// Absolute angle and rate of the second link, unused by the single pendulum
+    pub th2: f64,
+    pub w2: f64,
+    /// Motor armature current
+    pub i: f64,
 }
 
 impl Default for State {
@@ -16,25 +21,54 @@ impl Default for State {
 }
 
 impl State {
+    /// A state with the second link in line with the first
     pub fn from(x: f64, v: f64, w: f64, th: f64) -> Self {
-        State { x, v, w, th }
+        State {
+            x,
+            v,
+            w,
+            th,
+            th2: th,
+            w2: w,
+            i: 0.,
+        }
     }
 
-    pub fn update(&mut self, (vdot, v, wdot, w): (f64, f64, f64, f64), dt: f64) {
-        self.w += wdot * dt;
-        self.th += w * dt;
-        self.th = (self.th % (2. * PI) + 2. * PI) % (2. * PI);
-        self.v += vdot * dt;
-        self.x += v * dt;
+    pub fn with_second_link(self, th2: f64, w2: f64) -> Self {
+        State { th2, w2, ..self }
+    }
+
+    pub fn with_current(self, i: f64) -> Self {
+        State { i, ..self }
+    }
+
+    /// The state as `(position, velocity)` pairs for the integrators, then the current
+    pub fn to_vector(&self) -> [f64; 7] {
+        [self.x, self.v, self.th, self.w, self.th2, self.w2, self.i]
     }
 
-    pub fn after(&self, (vdot, v, wdot, w): (f64, f64, f64, f64), dt: f64) -> State {
-        let mut new_state = self.clone();
-        new_state.update((vdot, v, wdot, w), dt);
-        new_state
+    pub fn from_vector([x, v, th, w, th2, w2, i]: [f64; 7]) -> Self {
+        let mut state = State {
+            x,
+            v,
+            w,
+            th,
+            th2,
+            w2,
+            i,
+        };
+        state.normalize();
+        state
     }
 
-    pub fn unpack(&self) -> (f64, f64, f64, f64) {
-        (self.x, self.v, self.w, self.th)
+    /// Brings the angles back into `[0, 2 PI)`
+    pub fn normalize(&mut self) {
+        self.th = (self.th % (2. * PI) + 2. * PI) % (2. * PI);
+        self.th2 = (self.th2 % (2. * PI) + 2. * PI) % (2. * PI);
     }
 }
+
+/// Wraps an angle difference into `[-PI, PI)`
+pub fn wrap_angle(th: f64) -> f64 {
+    (th + PI).rem_euclid(2. * PI) - PI
+}
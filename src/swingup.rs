@@ -0,0 +1,71 @@
+use crate::{
+    controller::ControllerKind,
+    state::{wrap_angle, State},
+};
+
+/// Åström-Furuta energy pumping, which drives the pendulum energy towards the
+/// potential energy of the upright position by commanding a cart acceleration
+/// `a = sat(k g (E - E0) / E0 sign(w cos th)) - kx x - kv v`.
+/// The cart supplies the energies each time the law runs, and hands over to
+/// `balancer` once inside the capture region.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SwingUp {
+    pub k: f64,
+    /// Largest commanded acceleration, in multiples of g
+    pub n: f64,
+    pub kx: f64,
+    pub kv: f64,
+    pub capture_angle: f64,
+    pub capture_rate: f64,
+    pub fall_angle: f64,
+    pub balancer: ControllerKind,
+    pub balancing: bool,
+}
+
+impl Default for SwingUp {
+    fn default() -> Self {
+        SwingUp {
+            k: 5.,
+            n: 1.,
+            kx: 0.5,
+            kv: 1.,
+            capture_angle: 0.3,
+            capture_rate: 2.,
+            fall_angle: 0.8,
+            balancer: ControllerKind::Lqr,
+            balancing: false,
+        }
+    }
+}
+
+impl SwingUp {
+    /// Switches between pumping and balancing, returns true on hand-off to the
+    /// balancer. With `second_link` the outer link must be caught upright too.
+    pub fn update_mode(&mut self, state: &State, setpoint: f64, second_link: bool) -> bool {
+        let outer = second_link.then_some((state.th2, state.w2));
+        let mut links = [Some((state.th, state.w)), outer]
+            .into_iter()
+            .flatten()
+            .map(|(th, w)| (wrap_angle(th - setpoint).abs(), w.abs()));
+        if self.balancing {
+            self.balancing = links.all(|(err, _)| err < self.fall_angle);
+            false
+        } else {
+            self.balancing =
+                links.all(|(err, w)| err < self.capture_angle && w < self.capture_rate);
+            self.balancing
+        }
+    }
+
+    /// Cart acceleration pumping the pendulum's `energy` towards `target`
+    pub fn acceleration(&self, state: &State, energy: f64, target: f64, g: f64) -> f64 {
+        let e = (energy - target) / target;
+        (self.k * g * e * (state.w * libm::cos(state.th)).signum()).clamp(-self.n * g, self.n * g)
+            - self.kx * state.x
+            - self.kv * state.v
+    }
+
+    pub fn reset(&mut self) {
+        self.balancing = false;
+    }
+}
@@ -0,0 +1,53 @@
+use crate::state::State;
+
+/// Rail of finite length with hard end stops, `length` being the travel of
+/// the cart's centre. An impact reverses the cart velocity scaled by
+/// `restitution`, 0 for a dead stop and 1 for a perfectly elastic bounce.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Track {
+    pub enable: bool,
+    pub length: f64,
+    pub restitution: f64,
+    /// Impacts since reset, resting against a stop counting once
+    pub hits: u32,
+    contact: bool,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Track {
+            enable: false,
+            length: 1.,
+            restitution: 0.3,
+            hits: 0,
+            contact: false,
+        }
+    }
+}
+
+impl Track {
+    /// Keeps the cart between the stops, returning the change in cart velocity
+    /// if it hits one
+    pub fn collide(&mut self, state: &mut State) -> Option<f64> {
+        let bound = 0.5 * self.length;
+        if !self.enable || state.x.abs() < bound {
+            self.contact = false;
+            return None;
+        }
+        let side = state.x.signum();
+        state.x = side * bound;
+        if state.v * side <= 0. {
+            return None;
+        }
+        if !self.contact {
+            self.hits += 1;
+            self.contact = true;
+        }
+        Some(-(1. + self.restitution) * state.v)
+    }
+
+    pub fn reset(&mut self) {
+        self.hits = 0;
+        self.contact = false;
+    }
+}
@@ -1,25 +1,58 @@
 use egui_macroquad::egui;
-use std::{collections::VecDeque, f32::INFINITY};
+use std::{collections::VecDeque, ops::RangeInclusive};
 
 use egui::{
     epaint::Shadow,
     plot::{CoordinatesFormatter, Corner, HLine, Legend, Line, Plot, PlotBounds, PlotPoints},
-    Align, Align2, Color32, Context, DragValue, Frame, Layout, Pos2, Slider, Vec2,
+    Align, Align2, Button, Color32, Context, DragValue, Frame, Layout, Pos2, Slider, Ui, Vec2,
 };
 use macroquad::prelude::*;
 
-use crate::{
-    camera::CameraDynamics,
-    cart::{self, Cart},
-    state::State,
+use pid_balancer::{
+    actuator::Actuator,
+    cart::Cart,
+    controller::{AntiWindup, ControllerKind, DerivativeSource, Pid, PositionLoop},
+    disturbance::{Disturbance, Disturbances, Shape, Target},
+    friction::{Friction, FrictionModel},
+    integrator::{DormandPrince, IntegratorKind},
+    lqr::Lqr,
+    motor::Motor,
+    observer::{Estimator, Observer},
+    params::{CartParams, Plant},
+    sensor::Sensors,
+    swingup::SwingUp,
+    track::Track,
 };
 
+use crate::{camera::CameraDynamics, view::View};
+
+/// Longest stretch of history a graph keeps, in simulated seconds
+const HISTORY: f64 = 60.;
+
+/// Stretch of history the graphs show, shared by all of them
+pub struct Timeline {
+    /// Seconds shown
+    pub window: f64,
+    /// End of the shown stretch while held, the graphs otherwise following the simulation
+    pub hold: Option<f64>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline {
+            window: 5.,
+            hold: None,
+        }
+    }
+}
+
 pub struct Graph {
     title: &'static [&'static str],
     pos: Pos2,
     size: Vec2,
+    /// Simulation time of each sample
+    times: VecDeque<f64>,
     history: Vec<VecDeque<f32>>,
-    hsize: usize,
     colors: Vec<Color32>,
 }
 
@@ -37,8 +70,8 @@ impl Graph {
             title,
             pos,
             size,
+            times: VecDeque::new(),
             history: (0..title.len() - 1).map(|_| VecDeque::new()).collect(),
-            hsize: 100,
             colors: colors
                 .unwrap_or_else(|| (0..title.len() - 1).map(|_| Color32::WHITE).collect()),
         }
@@ -48,17 +81,47 @@ impl Graph {
         self.pos.y = y;
     }
 
-    pub fn update(&mut self, track: Vec<f64>) {
+    /// Records the values at simulation time `t`
+    pub fn update(&mut self, t: f64, track: Vec<f64>) {
         assert!(track.len() == self.history.len());
-        for (i, &v) in track.iter().enumerate() {
-            self.history[i].push_back(v as f32);
-            if self.history[i].len() > self.hsize {
-                self.history[i].pop_front();
+        match self.times.back() {
+            // nothing new while the simulation is paused
+            Some(&last) if t == last => return,
+            // the simulation was reset
+            Some(&last) if t < last => {
+                self.times.clear();
+                self.history.iter_mut().for_each(VecDeque::clear);
+            }
+            _ => {}
+        }
+        self.times.push_back(t);
+        for (history, &v) in self.history.iter_mut().zip(&track) {
+            history.push_back(v as f32);
+        }
+        while self.times.front().is_some_and(|&first| first < t - HISTORY) {
+            self.times.pop_front();
+            for history in &mut self.history {
+                history.pop_front();
             }
         }
     }
 
-    pub fn draw(&self, ctx: &Context, clamp: f64) {
+    /// Times of the oldest and newest samples kept
+    pub fn span(&self) -> Option<(f64, f64)> {
+        Some((*self.times.front()?, *self.times.back()?))
+    }
+
+    pub fn draw(&self, ctx: &Context, clamp: f64, timeline: &Timeline) {
+        // the window fills from the left until there is enough history
+        let end = timeline
+            .hold
+            .or(self.times.back().copied())
+            .unwrap_or(0.)
+            .max(timeline.window);
+        let start = end - timeline.window;
+        // one sample either side so the lines run to the edges
+        let first = self.times.partition_point(|&t| t < start).saturating_sub(1);
+        let last = (self.times.partition_point(|&t| t <= end) + 1).min(self.times.len());
         egui::Window::new(self.title[0])
             .frame(Frame {
                 inner_margin: egui::Margin::same(0.),
@@ -78,7 +141,8 @@ impl Graph {
                 Plot::new("example")
                     .width(self.size.x)
                     .height(self.size.y)
-                    .show_axes([false, false])
+                    .show_axes([true, false])
+                    .x_axis_formatter(|t, _| format!("{t:.0} s"))
                     .show_background(false)
                     .allow_drag(false)
                     .allow_zoom(false)
@@ -93,17 +157,17 @@ impl Graph {
                     .legend(Legend::default().position(egui::plot::Corner::RightBottom))
                     .show(ui, |plot_ui| {
                         plot_ui.set_plot_bounds(PlotBounds::from_min_max(
-                            [0., -clamp * 1.1],
-                            [self.hsize as f64, clamp * 1.1],
+                            [start, -clamp * 1.1],
+                            [end, clamp * 1.1],
                         ));
                         plot_ui.hline(HLine::new(0.).color(Color32::WHITE).width(1.));
                         for i in 0..self.history.len() {
                             plot_ui.line(
                                 Line::new(
-                                    self.history[i]
-                                        .iter()
-                                        .enumerate()
-                                        .map(|(i, &y)| [i as f64, y as f64])
+                                    self.times
+                                        .range(first..last)
+                                        .zip(self.history[i].range(first..last))
+                                        .map(|(&t, &y)| [t, y as f64])
                                         .collect::<PlotPoints>(),
                                 )
                                 .width(2.)
@@ -117,6 +181,7 @@ impl Graph {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_speedometer(
     label: &str,
     center: macroquad::math::Vec2,
@@ -177,7 +242,7 @@ pub fn draw_speedometer(
         (0.5 + center.x) * screen_width() - size.width * 0.5,
         0.5 * (screen_height() - center.y * screen_width()) + size.offset_y + size.height,
         TextParams {
-            font: font,
+            font,
             font_size: fsize as u16 * 2,
             font_scale: 0.5,
             color: Color::new(1., 1., 1., 0.75),
@@ -193,12 +258,25 @@ pub fn draw_speedometer(
         WHITE,
     )
 }
-pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forceplt1: &mut Graph) {
+#[allow(clippy::too_many_arguments)]
+pub fn draw_ui(
+    w: f32,
+    grid: f32,
+    cart: &mut Cart,
+    view: &mut View,
+    forceplt: &mut Graph,
+    forceplt1: &mut Graph,
+    motorplt: &mut Graph,
+    observerplt: &mut Graph,
+    timeline: &mut Timeline,
+) {
     egui_macroquad::ui(|ctx| {
         // ctx.set_debug_on_hover(true);
         ctx.set_pixels_per_point(screen_width() / w);
         forceplt.y(2.);
         forceplt1.y(2.);
+        motorplt.y(6. + grid * w);
+        observerplt.y(6. + grid * w);
         egui::Window::new("Controls")
             .anchor(Align2::RIGHT_TOP, egui::emath::vec2(0., 0.))
             .pivot(Align2::RIGHT_TOP)
@@ -208,46 +286,38 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
             .collapsible(false)
             .title_bar(false)
             .show(ctx, |ui| {
-                ui.with_layout(Layout::top_down(Align::RIGHT), |ui| {
-                    ui.add(
-                        Slider::new(&mut cart.pid.0, 0.0..=150.0)
-                            .drag_value_speed(0.2)
-                            .text("P"),
-                    );
-                    ui.add(
-                        Slider::new(&mut cart.pid.1, 0.0..=100.0)
-                            .drag_value_speed(0.1)
-                            .text("I"),
-                    );
-                    ui.add(
-                        Slider::new(&mut cart.pid.2, 0.0..=40.)
-                            .drag_value_speed(0.04)
-                            .text("D"),
-                    );
-                });
+                if cart.controller == ControllerKind::SwingUp {
+                    swing_up_controls(ui, &mut cart.swing_up);
+                    ui.separator();
+                }
+                match cart.balancer() {
+                    ControllerKind::Lqr => lqr_controls(ui, &mut cart.lqr, cart.plant),
+                    _ => pid_controls(ui, &mut cart.pid),
+                }
                 ui.separator();
+                let cascade = cart.balancer() != ControllerKind::Lqr;
+                position_controls(ui, &mut cart.position, cascade);
                 ui.separator();
+                match cart.plant {
+                    Plant::Double => {
+                        second_link_controls(ui, &mut cart.params);
+                        ui.separator();
+                    }
+                    Plant::Furuta => {
+                        arm_controls(ui, &mut cart.params);
+                        ui.separator();
+                    }
+                    Plant::Single => {}
+                }
                 ui.columns(2, |cols| {
                     cols[0].with_layout(Layout::top_down(Align::Max), |ui| {
-                        ui.horizontal(|ui| {
-                            ui.add(
-                                DragValue::new(&mut cart.M)
-                                    .clamp_range(0.0..=100.)
-                                    .speed(0.05),
-                            );
-                            ui.label("M_cart");
+                        mass_value(ui, cart.params.M, "M_cart", |v| {
+                            cart.params.set_cart_mass(v)
                         });
+                        mass_value(ui, cart.params.ml, "M_rod", |v| cart.params.set_rod_mass(v));
                         ui.horizontal(|ui| {
                             ui.add(
-                                DragValue::new(&mut cart.ml)
-                                    .clamp_range(0.0..=100.)
-                                    .speed(0.05),
-                            );
-                            ui.label("M_rod");
-                        });
-                        ui.horizontal(|ui| {
-                            ui.add(
-                                DragValue::new(&mut cart.b1)
+                                DragValue::new(&mut cart.params.b1)
                                     .clamp_range(0.0..=0.5)
                                     .speed(0.0002)
                                     .custom_formatter(|x, _| format!("{:.3}", x)),
@@ -256,7 +326,7 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
                         });
                         ui.horizontal(|ui| {
                             ui.add(
-                                DragValue::new(&mut cart.l)
+                                DragValue::new(&mut cart.params.l)
                                     .clamp_range(0.1..=10.)
                                     .speed(0.05),
                             );
@@ -265,32 +335,20 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
                         ui.horizontal(|ui| {
                             ui.add(
                                 DragValue::new(&mut cart.Fclamp)
-                                    .clamp_range(0.0..=INFINITY)
+                                    .clamp_range(0.0..=f32::INFINITY)
                                     .speed(1.),
                             );
                             ui.label("F_clamp");
                         });
                     });
                     cols[1].with_layout(Layout::top_down(Align::Max), |ui| {
-                        ui.horizontal(|ui| {
-                            ui.add(
-                                DragValue::new(&mut cart.m)
-                                    .clamp_range(0.0..=100.)
-                                    .speed(0.05),
-                            );
-                            ui.label("M_bob");
+                        mass_value(ui, cart.params.m, "M_bob", |v| cart.params.set_bob_mass(v));
+                        mass_value(ui, cart.params.mw, "M_wheel", |v| {
+                            cart.params.set_wheel_mass(v)
                         });
                         ui.horizontal(|ui| {
                             ui.add(
-                                DragValue::new(&mut cart.mw)
-                                    .clamp_range(0.0..=100.)
-                                    .speed(0.05),
-                            );
-                            ui.label("M_wheel");
-                        });
-                        ui.horizontal(|ui| {
-                            ui.add(
-                                DragValue::new(&mut cart.b2)
+                                DragValue::new(&mut cart.params.b2)
                                     .clamp_range(0.0..=0.5)
                                     .speed(0.0002)
                                     .custom_formatter(|x, _| format!("{:.3}", x)),
@@ -299,8 +357,8 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
                         });
                         ui.horizontal(|ui| {
                             ui.add(
-                                DragValue::new(&mut cart.R)
-                                    .clamp_range(0.0..=1.)
+                                DragValue::new(&mut cart.params.R)
+                                    .clamp_range(0.01..=1.)
                                     .speed(0.005),
                             );
                             ui.label("R_wheel");
@@ -308,13 +366,23 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
                         ui.horizontal(|ui| {
                             ui.add(
                                 DragValue::new(&mut cart.Finp)
-                                    .clamp_range(0.0..=INFINITY)
+                                    .clamp_range(0.0..=f32::INFINITY)
                                     .speed(1.),
                             );
                             ui.label("Input Force");
                         });
                     });
                 });
+                ui.separator();
+                friction_controls(ui, &mut cart.friction);
+                ui.separator();
+                motor_controls(ui, &mut cart.motor);
+                actuator_controls(ui, &mut cart.actuator);
+                ui.separator();
+                sensor_controls(ui, &mut cart.sensors);
+                observer_controls(ui, &mut cart.observer);
+                ui.separator();
+                disturbance_controls(ui, &mut cart.disturbances);
             });
 
         egui::Window::new("Physics")
@@ -332,24 +400,97 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
                         "Potential Energy: {:.2}",
                         cart.get_potential_energy()
                     ));
-                    ui.separator();
                     ui.horizontal(|ui| {
+                        let drift = cart.get_energy_drift();
+                        ui.label(format!(
+                            "Energy Drift: {:+.2e} ({:+.3}%)",
+                            drift,
+                            100. * drift / cart.energy_reference.abs().max(1e-9)
+                        ));
+                        if ui.small_button("Zero").clicked() {
+                            cart.reset_energy_drift();
+                        }
+                    });
+                    ui.separator();
+                    ui.horizontal_wrapped(|ui| {
                         ui.label("Integrator: ");
-                        ui.selectable_value(&mut cart.integrator, cart::Integrator::Euler, "Euler");
+                        for (integrator, name) in [
+                            (IntegratorKind::Euler, "Euler"),
+                            (IntegratorKind::SemiImplicitEuler, "Semi-implicit Euler"),
+                            (IntegratorKind::VelocityVerlet, "Velocity Verlet"),
+                            (IntegratorKind::Leapfrog, "Leapfrog"),
+                            (IntegratorKind::RungeKutta4, "Runge-Kutta⁴"),
+                            (IntegratorKind::DormandPrince, "Dormand-Prince⁵"),
+                        ] {
+                            ui.selectable_value(&mut cart.integrator, integrator, name);
+                        }
+                    });
+                    if cart.integrator == IntegratorKind::DormandPrince {
+                        adaptive_controls(ui, &mut cart.adaptive);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Plant: ");
+                        for (plant, name) in [
+                            (Plant::Single, "Pendulum"),
+                            (Plant::Double, "Double pendulum"),
+                            (Plant::Furuta, "Furuta"),
+                        ] {
+                            if ui.selectable_value(&mut cart.plant, plant, name).changed() {
+                                cart.reset();
+                            }
+                        }
+                    });
+                    if cart.plant != Plant::Furuta {
+                        track_controls(ui, &mut cart.track);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Controller: ");
+                        ui.selectable_value(&mut cart.controller, ControllerKind::Pid, "PID");
+                        ui.selectable_value(&mut cart.controller, ControllerKind::Lqr, "LQR");
                         ui.selectable_value(
-                            &mut cart.integrator,
-                            cart::Integrator::RungeKutta4,
-                            "Runge-Kutta⁴",
+                            &mut cart.controller,
+                            ControllerKind::SwingUp,
+                            "Swing-up",
                         );
+                        if cart.custom.is_some() {
+                            ui.selectable_value(
+                                &mut cart.controller,
+                                ControllerKind::Custom,
+                                "Custom",
+                            );
+                        }
                     });
                     ui.separator();
                     ui.add(
                         Slider::new(&mut cart.steps, 1..=100)
                             .logarithmic(true)
-                            .text("Steps / Frame"),
+                            .custom_formatter(|n, _| format!("{} Hz", n * 60.))
+                            .custom_parser(|s| {
+                                s.trim_end_matches("Hz")
+                                    .trim()
+                                    .parse::<f64>()
+                                    .map(|v| v / 60.)
+                                    .ok()
+                            })
+                            .text("Physics Rate"),
                     );
+                    time_controls(ui, cart);
+                    timeline_controls(ui, timeline, forceplt.span());
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut cart.discrete, "Sampled controller");
+                        for rate in [50., 200., 1000.] {
+                            ui.selectable_value(&mut cart.sample_rate, rate, format!("{rate} Hz"));
+                        }
+                    });
+                    if cart.discrete {
+                        ui.add(
+                            Slider::new(&mut cart.sample_rate, 5.0..=2000.0)
+                                .logarithmic(true)
+                                .text("Sample Rate (Hz)"),
+                        );
+                    }
                     ui.add(
-                        Slider::new(&mut cart.ui_scale, 0.03..=0.6)
+                        Slider::new(&mut view.scale, 0.03..=0.6)
                             .custom_formatter(|n, _| format!("{:.2}", n / 0.3))
                             .custom_parser(|s| s.parse::<f64>().map(|v| v * 0.3).ok())
                             .text("Draw Scale"),
@@ -367,23 +508,683 @@ pub fn draw_ui(w: f32, grid: f32, cart: &mut Cart, forceplt: &mut Graph, forcepl
                             },
                         );
                         if ui.button("Reset").clicked() {
-                            cart.state = State::default();
-                            cart.int = 0.;
-                            cart.camera = CameraDynamics::default();
+                            cart.reset();
+                            view.camera = CameraDynamics::default();
                         };
                     })
                 });
             });
-        forceplt.draw(ctx, cart.Fclamp);
-        forceplt1.draw(ctx, 9.);
+        if !ctx.wants_keyboard_input() {
+            time_shortcuts(cart);
+        }
+        if cart.motor.enable {
+            let stall = cart.motor.v_max / cart.motor.Ra;
+            let r = cart.params.drive_radius(cart.plant);
+            forceplt.draw(ctx, cart.motor.force(stall, r), timeline);
+            motorplt.draw(ctx, cart.motor.v_max.max(stall), timeline);
+        } else {
+            forceplt.draw(ctx, cart.Fclamp, timeline);
+        }
+        forceplt1.draw(ctx, 9., timeline);
+        if cart.observer.source != Estimator::Measured {
+            observerplt.draw(ctx, 3., timeline);
+        }
     });
     egui_macroquad::draw();
 }
 
+fn adaptive_controls(ui: &mut Ui, adaptive: &mut DormandPrince) {
+    for (value, label) in [
+        (&mut adaptive.atol, "Abs Tolerance"),
+        (&mut adaptive.rtol, "Rel Tolerance"),
+    ] {
+        ui.add(
+            Slider::new(value, 1e-12..=1e-1)
+                .logarithmic(true)
+                .custom_formatter(|x, _| format!("{:.0e}", x))
+                .text(label),
+        );
+    }
+    ui.label(format!(
+        "Steps: {} accepted, {} rejected, h = {:.2e} s",
+        adaptive.accepted, adaptive.rejected, adaptive.h
+    ));
+    if adaptive.failed {
+        ui.colored_label(
+            Color32::LIGHT_RED,
+            "The solution blew up, reset or change the parameters",
+        );
+    } else if adaptive.stalled {
+        ui.colored_label(
+            Color32::LIGHT_RED,
+            "Tolerance not met at the minimum step size",
+        );
+    }
+}
+
+fn pid_controls(ui: &mut Ui, pid: &mut Pid) {
+    ui.with_layout(Layout::top_down(Align::RIGHT), |ui| {
+        ui.add(
+            Slider::new(&mut pid.kp, 0.0..=1500.0)
+                .drag_value_speed(2.)
+                .text("P"),
+        );
+        ui.add(
+            Slider::new(&mut pid.ki, 0.0..=1000.0)
+                .drag_value_speed(1.)
+                .text("I"),
+        );
+        ui.add(
+            Slider::new(&mut pid.kd, 0.0..=400.)
+                .drag_value_speed(0.4)
+                .text("D"),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Anti-windup:");
+        ui.selectable_value(&mut pid.anti_windup, AntiWindup::None, "None");
+        ui.selectable_value(&mut pid.anti_windup, AntiWindup::Clamping, "Clamp");
+        ui.selectable_value(&mut pid.anti_windup, AntiWindup::Conditional, "Cond.");
+        ui.selectable_value(
+            &mut pid.anti_windup,
+            AntiWindup::BackCalculation,
+            "Back-calc",
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("Derivative:");
+        ui.selectable_value(
+            &mut pid.derivative_source,
+            DerivativeSource::Velocity,
+            "True ω",
+        );
+        ui.selectable_value(
+            &mut pid.derivative_source,
+            DerivativeSource::Sampled,
+            "Sampled",
+        );
+        ui.selectable_value(&mut pid.derivative_on_error, true, "On error");
+        ui.selectable_value(&mut pid.derivative_on_error, false, "On meas.");
+    });
+    if pid.derivative_source == DerivativeSource::Sampled {
+        let tf = pid.filter_time_constant();
+        ui.with_layout(Layout::top_down(Align::RIGHT), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut pid.filter_n)
+                        .clamp_range(0.0..=1000.)
+                        .speed(0.1),
+                );
+                ui.label(format!("Filter N (T_f = {:.4} s)", tf));
+            });
+        });
+    }
+    let (value, label) = match pid.anti_windup {
+        AntiWindup::Clamping => (&mut pid.int_limit, "Integral limit"),
+        AntiWindup::BackCalculation => (&mut pid.tracking, "Tracking T_t (s)"),
+        _ => return,
+    };
+    ui.with_layout(Layout::top_down(Align::RIGHT), |ui| {
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(value)
+                    .clamp_range(0.001..=10.)
+                    .speed(0.005)
+                    .custom_formatter(|x, _| format!("{:.3}", x)),
+            );
+            ui.label(label);
+        });
+    });
+}
+
+fn position_controls(ui: &mut Ui, position: &mut PositionLoop, cascade: bool) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut position.enable, "Position loop");
+        ui.add(
+            DragValue::new(&mut position.target)
+                .speed(0.01)
+                .custom_formatter(|x, _| format!("{:.2}", x)),
+        );
+        ui.label("Target");
+    });
+    if position.enable && cascade {
+        ui.with_layout(Layout::top_down(Align::RIGHT), |ui| {
+            ui.add(
+                Slider::new(&mut position.kp, 0.0..=0.5)
+                    .drag_value_speed(0.001)
+                    .text("P_x"),
+            );
+            ui.add(
+                Slider::new(&mut position.ki, 0.0..=0.2)
+                    .drag_value_speed(0.001)
+                    .text("I_x"),
+            );
+            ui.add(
+                Slider::new(&mut position.kd, 0.0..=1.)
+                    .drag_value_speed(0.002)
+                    .text("D_x"),
+            );
+        });
+    }
+}
+
+fn lqr_controls(ui: &mut Ui, lqr: &mut Lqr, plant: Plant) {
+    let weight = |ui: &mut Ui, value: &mut f64, label: &str| {
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(value)
+                    .clamp_range(0.0..=1e4)
+                    .speed(0.1)
+                    .custom_formatter(|x, _| format!("{:.3}", x)),
+            );
+            ui.label(label);
+        });
+    };
+    ui.columns(2, |cols| {
+        cols[0].with_layout(Layout::top_down(Align::Max), |ui| {
+            weight(ui, &mut lqr.q[0], "Q_x");
+            weight(ui, &mut lqr.q[2], "Q_th");
+            if plant == Plant::Double {
+                weight(ui, &mut lqr.q[4], "Q_th2");
+            }
+            weight(ui, &mut lqr.r, "R");
+        });
+        cols[1].with_layout(Layout::top_down(Align::Max), |ui| {
+            weight(ui, &mut lqr.q[1], "Q_v");
+            weight(ui, &mut lqr.q[3], "Q_w");
+            if plant == Plant::Double {
+                weight(ui, &mut lqr.q[5], "Q_w2");
+            }
+        });
+    });
+    lqr.r = lqr.r.max(1e-4);
+    ui.with_layout(Layout::top_down(Align::Center), |ui| {
+        if lqr.solved {
+            let n = if plant == Plant::Double { 6 } else { 4 };
+            let k: Vec<_> = lqr.k[..n].iter().map(|k| format!("{k:.1}")).collect();
+            ui.label(format!("K = [{}]", k.join(", ")));
+        } else {
+            ui.label("K = no stabilizing solution");
+        }
+    });
+}
+
+/// Drags a copy of a mass, handing changes to `set` for the model to validate
+fn mass_value(ui: &mut Ui, mut value: f64, label: &str, set: impl FnOnce(f64)) {
+    ui.horizontal(|ui| {
+        let drag = DragValue::new(&mut value)
+            .clamp_range(0.0..=100.)
+            .speed(0.05);
+        if ui.add(drag).changed() {
+            set(value);
+        }
+        ui.label(label);
+    });
+}
+
+fn second_link_controls(ui: &mut Ui, params: &mut CartParams) {
+    let value = |ui: &mut Ui, value: &mut f64, min: f64, speed: f64, label: &str| {
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(value)
+                    .clamp_range(min..=100.)
+                    .speed(speed)
+                    .custom_formatter(|x, _| format!("{:.3}", x)),
+            );
+            ui.label(label);
+        });
+    };
+    ui.columns(2, |cols| {
+        cols[0].with_layout(Layout::top_down(Align::Max), |ui| {
+            mass_value(ui, params.ml_2, "M_rod2", |v| params.set_second_rod_mass(v));
+            value(ui, &mut params.l_2, 0.1, 0.05, "L_rod2");
+        });
+        cols[1].with_layout(Layout::top_down(Align::Max), |ui| {
+            mass_value(ui, params.m_2, "M_bob2", |v| params.set_second_bob_mass(v));
+            value(ui, &mut params.b3, 0., 0.0002, "Ang_Drag2");
+        });
+    });
+}
+
+fn sensor_controls(ui: &mut Ui, sensors: &mut Sensors) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut sensors.enable, "Sensors");
+        if sensors.enable {
+            if ui
+                .add(DragValue::new(&mut sensors.seed).prefix("seed "))
+                .changed()
+            {
+                sensors.reseed();
+            }
+            ui.add(
+                DragValue::new(&mut sensors.cpr)
+                    .clamp_range(0..=1 << 20)
+                    .speed(16.)
+                    .suffix(" CPR"),
+            );
+        }
+    });
+    if !sensors.enable {
+        return;
+    }
+    ui.columns(2, |cols| {
+        for (ui, (channel, name)) in cols
+            .iter_mut()
+            .zip([(&mut sensors.position, "x"), (&mut sensors.angle, "θ")])
+        {
+            ui.with_layout(Layout::top_down(Align::Max), |ui| {
+                let mut value = |value: &mut f64, label: &str| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(value)
+                                .clamp_range(-1.0..=1.)
+                                .speed(0.0001)
+                                .custom_formatter(|x, _| format!("{:.4}", x)),
+                        );
+                        ui.label(format!("{label} {name}"));
+                    });
+                };
+                value(&mut channel.noise, "σ");
+                value(&mut channel.bias, "Bias");
+                value(&mut channel.quantization, "LSB");
+            });
+        }
+    });
+    sensors.position.noise = sensors.position.noise.max(0.);
+    sensors.angle.noise = sensors.angle.noise.max(0.);
+    sensors.position.quantization = sensors.position.quantization.max(0.);
+    sensors.angle.quantization = sensors.angle.quantization.max(0.);
+}
+
+/// Slowest and fastest simulation speed relative to real time
+const TIME_SCALE: RangeInclusive<f64> = 0.1..=10.;
+
+fn time_controls(ui: &mut Ui, cart: &mut Cart) {
+    ui.horizontal(|ui| {
+        ui.toggle_value(&mut cart.paused, "Pause")
+            .on_hover_text("Space");
+        if ui
+            .add_enabled(cart.paused, Button::new("Step"))
+            .on_hover_text(".")
+            .clicked()
+        {
+            cart.step_once();
+        }
+        ui.add(
+            Slider::new(&mut cart.time_scale, TIME_SCALE)
+                .logarithmic(true)
+                .custom_formatter(|n, _| format!("{n:.2}x"))
+                .custom_parser(|s| s.trim_end_matches('x').trim().parse().ok())
+                .text("Speed"),
+        )
+        .on_hover_text("[ and ] halve and double");
+    });
+}
+
+/// Window length, and holding the graphs still to scroll back through them
+fn timeline_controls(ui: &mut Ui, timeline: &mut Timeline, span: Option<(f64, f64)>) {
+    ui.add(
+        Slider::new(&mut timeline.window, 1.0..=HISTORY)
+            .logarithmic(true)
+            .suffix(" s")
+            .text("Graph Window"),
+    );
+    ui.horizontal(|ui| {
+        let mut hold = timeline.hold.is_some();
+        if ui.toggle_value(&mut hold, "Hold graphs").changed() {
+            timeline.hold = hold.then(|| span.map_or(0., |(_, last)| last));
+        }
+        if let (Some(end), Some((first, last))) = (&mut timeline.hold, span) {
+            let earliest = (first + timeline.window).min(last);
+            *end = end.clamp(earliest, last);
+            ui.add(
+                Slider::new(end, earliest..=last)
+                    .suffix(" s")
+                    .text("Scroll"),
+            );
+        }
+    });
+}
+
+/// Space pauses, `.` steps while paused, and `[` and `]` halve and double the speed
+fn time_shortcuts(cart: &mut Cart) {
+    if is_key_pressed(KeyCode::Space) {
+        cart.paused = !cart.paused;
+    }
+    if is_key_pressed(KeyCode::Period) && cart.paused {
+        cart.step_once();
+    }
+    if is_key_pressed(KeyCode::LeftBracket) {
+        cart.time_scale *= 0.5;
+    }
+    if is_key_pressed(KeyCode::RightBracket) {
+        cart.time_scale *= 2.;
+    }
+    cart.time_scale = cart
+        .time_scale
+        .clamp(*TIME_SCALE.start(), *TIME_SCALE.end());
+}
+
+fn disturbance_controls(ui: &mut Ui, disturbances: &mut Disturbances) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut disturbances.enable, "Disturbances");
+        if disturbances.enable && ui.button("Add").clicked() {
+            disturbances.list.push(Disturbance::default());
+        }
+    });
+    if !disturbances.enable {
+        return;
+    }
+    let mut remove = None;
+    for (n, d) in disturbances.list.iter_mut().enumerate() {
+        ui.push_id(n, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut d.start)
+                        .clamp_range(0.0..=1e6)
+                        .speed(0.1)
+                        .prefix("at "),
+                );
+                ui.selectable_value(&mut d.target, Target::Cart, "Cart");
+                ui.selectable_value(&mut d.target, Target::Pendulum, "Pendulum");
+                if ui.button("✖").clicked() {
+                    remove = Some(n);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut d.shape, Shape::Impulse, "Impulse");
+                ui.selectable_value(&mut d.shape, Shape::Step, "Step");
+                ui.selectable_value(&mut d.shape, Shape::Sine, "Sine");
+            });
+            ui.horizontal(|ui| {
+                ui.add(DragValue::new(&mut d.amplitude).speed(0.1).prefix("A "));
+                ui.add(
+                    DragValue::new(&mut d.duration)
+                        .clamp_range(1e-3..=f64::INFINITY)
+                        .speed(0.01)
+                        .prefix("for "),
+                );
+                if d.shape == Shape::Sine {
+                    ui.add(
+                        DragValue::new(&mut d.frequency)
+                            .clamp_range(0.0..=50.)
+                            .speed(0.01)
+                            .suffix(" Hz"),
+                    );
+                }
+                ui.add(
+                    DragValue::new(&mut d.repeat)
+                        .clamp_range(0.0..=1e6)
+                        .speed(0.1)
+                        .prefix("every "),
+                );
+                // a repeat shorter than the disturbance would overlap itself
+                if d.repeat != 0. && d.repeat < d.duration {
+                    d.repeat = if d.duration <= 1e6 { d.duration } else { 0. };
+                }
+            });
+        });
+    }
+    if let Some(n) = remove {
+        disturbances.list.remove(n);
+    }
+    scenario_file(ui, disturbances);
+}
+
+/// Path and last result of the scenario file buttons, kept in egui's memory
+#[cfg(not(target_arch = "wasm32"))]
+fn scenario_file(ui: &mut Ui, disturbances: &mut Disturbances) {
+    let id = ui.id().with("scenario");
+    let (mut path, mut status) = ui
+        .data_mut(|d| d.get_temp::<(String, String)>(id))
+        .unwrap_or_else(|| ("scenario.txt".to_owned(), String::new()));
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut path).desired_width(100.));
+        if ui.button("Load").clicked() {
+            let read = std::fs::read_to_string(&path).map_err(|e| e.to_string());
+            status = match read.and_then(|text| Disturbances::parse(&text)) {
+                Ok(list) => {
+                    disturbances.list = list;
+                    "Loaded".to_owned()
+                }
+                Err(e) => e,
+            };
+        }
+        if ui.button("Save").clicked() {
+            status = match std::fs::write(&path, disturbances.to_string()) {
+                Ok(()) => "Saved".to_owned(),
+                Err(e) => e.to_string(),
+            };
+        }
+    });
+    if !status.is_empty() {
+        ui.label(&status);
+    }
+    ui.data_mut(|d| d.insert_temp(id, (path, status)));
+}
+
+/// The web build has no file system to read scenarios from
+#[cfg(target_arch = "wasm32")]
+fn scenario_file(_: &mut Ui, _: &mut Disturbances) {}
+
+fn observer_controls(ui: &mut Ui, observer: &mut Observer) {
+    ui.horizontal(|ui| {
+        ui.label("Estimate");
+        ui.selectable_value(&mut observer.source, Estimator::Measured, "Measured");
+        ui.selectable_value(&mut observer.source, Estimator::Luenberger, "Luenberger");
+        ui.selectable_value(&mut observer.source, Estimator::Kalman, "Kalman");
+    });
+    ui.horizontal(|ui| match observer.source {
+        Estimator::Measured => {}
+        Estimator::Luenberger => {
+            ui.add(
+                DragValue::new(&mut observer.weight)
+                    .clamp_range(1.0..=1e6)
+                    .speed(10.),
+            );
+            ui.label("Observer Q/R");
+        }
+        Estimator::Kalman => {
+            ui.add(
+                DragValue::new(&mut observer.process_noise)
+                    .clamp_range(1e-3..=100.)
+                    .speed(0.01),
+            );
+            ui.label("Process σ");
+        }
+    });
+}
+
+fn actuator_controls(ui: &mut Ui, actuator: &mut Actuator) {
+    ui.add(
+        Slider::new(&mut actuator.delay, 0.0..=0.5)
+            .custom_formatter(|x, _| format!("{:.0} ms", x * 1000.))
+            .custom_parser(|s| {
+                s.trim_end_matches("ms")
+                    .trim()
+                    .parse::<f64>()
+                    .map(|v| v / 1000.)
+                    .ok()
+            })
+            .text("Dead Time"),
+    );
+    ui.add(
+        Slider::new(&mut actuator.dead_band, 0.0..=50.0)
+            .logarithmic(true)
+            .text("Dead-band"),
+    );
+    ui.horizontal(|ui| {
+        let mut limited = actuator.slew_rate.is_finite();
+        if ui.checkbox(&mut limited, "Slew limit").changed() {
+            actuator.slew_rate = if limited { 2000. } else { f64::INFINITY };
+        }
+        if limited {
+            ui.add(
+                Slider::new(&mut actuator.slew_rate, 1.0..=1e5)
+                    .logarithmic(true)
+                    .text("per s"),
+            );
+        }
+    });
+}
+
+fn motor_controls(ui: &mut Ui, motor: &mut Motor) {
+    ui.checkbox(&mut motor.enable, "DC motor (controller outputs volts)");
+    if !motor.enable {
+        return;
+    }
+    let value = |ui: &mut Ui, value: &mut f64, speed: f64, label: &str| {
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(value)
+                    .clamp_range(1e-4..=1000.)
+                    .speed(speed)
+                    .custom_formatter(|x, _| format!("{:.3}", x)),
+            );
+            ui.label(label);
+        });
+    };
+    ui.columns(2, |cols| {
+        cols[0].with_layout(Layout::top_down(Align::Max), |ui| {
+            value(ui, &mut motor.Ra, 0.01, "R_a (Ω)");
+            value(ui, &mut motor.Kt, 0.001, "K_t");
+            value(ui, &mut motor.gear, 0.1, "Gear");
+        });
+        cols[1].with_layout(Layout::top_down(Align::Max), |ui| {
+            value(ui, &mut motor.La, 0.0005, "L_a (H)");
+            value(ui, &mut motor.Ke, 0.001, "K_e");
+            value(ui, &mut motor.v_max, 0.1, "V_max");
+        });
+    });
+}
+
+fn friction_controls(ui: &mut Ui, friction: &mut Friction) {
+    ui.horizontal(|ui| {
+        ui.label("Dry friction: ");
+        ui.selectable_value(&mut friction.model, FrictionModel::None, "None");
+        ui.selectable_value(&mut friction.model, FrictionModel::Coulomb, "Coulomb");
+        ui.selectable_value(&mut friction.model, FrictionModel::Stribeck, "Stribeck");
+    });
+    if friction.model == FrictionModel::None {
+        return;
+    }
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut friction.stick_slip, false, "Smooth");
+        ui.selectable_value(&mut friction.stick_slip, true, "Stick-slip");
+        ui.add(
+            DragValue::new(&mut friction.band)
+                .clamp_range(1e-4..=1.)
+                .speed(0.0005)
+                .custom_formatter(|x, _| format!("{:.4}", x)),
+        );
+        ui.label("v_band");
+    });
+    let stribeck = friction.model == FrictionModel::Stribeck;
+    ui.columns(2, |cols| {
+        for (ui, (joint, name)) in cols
+            .iter_mut()
+            .zip([(&mut friction.cart, "Cart"), (&mut friction.pivot, "Pivot")])
+        {
+            ui.with_layout(Layout::top_down(Align::Max), |ui| {
+                let mut value = |value: &mut f64, label: &str| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(value)
+                                .clamp_range(0.0..=100.)
+                                .speed(0.005)
+                                .custom_formatter(|x, _| format!("{:.3}", x)),
+                        );
+                        ui.label(format!("{name} {label}"));
+                    });
+                };
+                value(&mut joint.coulomb, "F_c");
+                if stribeck {
+                    value(&mut joint.breakaway, "F_s");
+                    value(&mut joint.stribeck_velocity, "v_s");
+                }
+            });
+        }
+    });
+}
+
+fn track_controls(ui: &mut Ui, track: &mut Track) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut track.enable, "End stops");
+        if track.enable {
+            ui.label(format!("Hits: {}", track.hits));
+        }
+    });
+    if track.enable {
+        ui.add(Slider::new(&mut track.length, 0.2..=10.0).text("Track Length (m)"));
+        ui.add(Slider::new(&mut track.restitution, 0.0..=1.0).text("Restitution"));
+    }
+}
+
+fn arm_controls(ui: &mut Ui, params: &mut CartParams) {
+    ui.columns(2, |cols| {
+        cols[0].with_layout(Layout::top_down(Align::Max), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut params.l_arm)
+                        .clamp_range(0.1..=10.)
+                        .speed(0.05),
+                );
+                ui.label("L_arm");
+            });
+        });
+        cols[1].with_layout(Layout::top_down(Align::Max), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut params.J_arm)
+                        .clamp_range(0.0..=100.)
+                        .speed(0.05),
+                );
+                ui.label("J_arm");
+            });
+        });
+    });
+}
+
+fn swing_up_controls(ui: &mut Ui, swing_up: &mut SwingUp) {
+    let gain = |ui: &mut Ui, value: &mut f64, max: f64, label: &str| {
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(value)
+                    .clamp_range(0.0..=max)
+                    .speed(0.01)
+                    .custom_formatter(|x, _| format!("{:.2}", x)),
+            );
+            ui.label(label);
+        });
+    };
+    ui.columns(2, |cols| {
+        cols[0].with_layout(Layout::top_down(Align::Max), |ui| {
+            gain(ui, &mut swing_up.k, 20., "k_E");
+            gain(ui, &mut swing_up.kx, 10., "k_x");
+            gain(ui, &mut swing_up.capture_angle, 1.5, "Capture");
+            gain(ui, &mut swing_up.fall_angle, 3., "Fall");
+        });
+        cols[1].with_layout(Layout::top_down(Align::Max), |ui| {
+            gain(ui, &mut swing_up.n, 5., "a_max (g)");
+            gain(ui, &mut swing_up.kv, 10., "k_v");
+            gain(ui, &mut swing_up.capture_rate, 20., "Capture ω");
+        });
+    });
+    ui.horizontal(|ui| {
+        ui.label(if swing_up.balancing {
+            "Balancing with:"
+        } else {
+            "Swinging, then:"
+        });
+        ui.selectable_value(&mut swing_up.balancer, ControllerKind::Pid, "PID");
+        ui.selectable_value(&mut swing_up.balancer, ControllerKind::Lqr, "LQR");
+    });
+}
+
 pub fn draw_blue_grid(grid: f32, color: Color, thickness: f32, bold_every: i32, bold_thick: f32) {
     draw_line(0., -1., 0., 1., bold_thick, color);
     draw_line(-1., 0., 1., 0., bold_thick, color);
-    for i in 1..=(1. / grid as f32) as i32 {
+    for i in 1..=(1. / grid) as i32 {
         let thickness = if i % bold_every == 0 {
             bold_thick
         } else {
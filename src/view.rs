@@ -0,0 +1,268 @@
+#![allow(non_snake_case)]
+
+use macroquad::prelude::*;
+use pid_balancer::{cart::Cart, params::Plant};
+
+use crate::camera::CameraDynamics;
+
+pub struct View {
+    pub camera: CameraDynamics,
+    pub scale: f32,
+    dragging_target: bool,
+    /// Pointer holding the bob, on screen
+    hand: Option<Vec2>,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View {
+            camera: CameraDynamics::default(),
+            scale: 0.3,
+            dragging_target: false,
+            hand: None,
+        }
+    }
+}
+
+impl View {
+    pub fn update(&mut self, cart: &Cart, dt: f64) {
+        let state = cart.interpolated();
+        // the rotary plant stays put, its x being the arm angle
+        match cart.plant {
+            Plant::Furuta => self.camera.update(0., 0., dt),
+            _ => self.camera.update(state.x, state.v, dt),
+        }
+    }
+
+    fn to_screen(&self, x: f64) -> f32 {
+        (x - self.camera.y) as f32 * self.scale
+    }
+
+    fn to_world(&self, x: f32) -> f64 {
+        (x / self.scale) as f64 + self.camera.y
+    }
+
+    /// Moves the position target when the marker or the track under it is clicked and dragged
+    pub fn drag_target(&mut self, cart: &mut Cart, mouse: Vec2, depth: f32) {
+        if !cart.position.enable || cart.plant == Plant::Furuta {
+            return;
+        }
+        if is_mouse_button_pressed(MouseButton::Left) && (mouse.y + depth).abs() < 0.04 {
+            self.dragging_target = true;
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.dragging_target = false;
+        }
+        if self.dragging_target {
+            cart.position.target = self.to_world(mouse.x);
+        }
+    }
+
+    /// Screen height of the pendulum's pivot
+    fn pivot_height(&self, cart: &Cart, depth: f32) -> f32 {
+        let R = cart.params.R as f32 * self.scale;
+        match cart.plant {
+            Plant::Furuta => -depth + cart.params.l as f32 * self.scale + 2. * R,
+            _ => -depth + 5.5 * R,
+        }
+    }
+
+    /// Pulls the bob towards the pointer while it is clicked and held, or,
+    /// when paused, swings the pendulum round to point at it
+    pub fn drag_bob(&mut self, cart: &mut Cart, mouse: Vec2, depth: f32) {
+        let pivot = self.pivot_height(cart, depth);
+        let [x, v, th, w, th2, w2, _] = cart.interpolated().to_vector();
+        if is_mouse_button_pressed(MouseButton::Left) && !self.dragging_target {
+            let [bx, by] = cart.bob(&[x, v, th, w, th2, w2]);
+            let bob = vec2(self.to_screen(bx), pivot + by as f32 * self.scale);
+            let reach = 1.5 * (cart.params.R as f32 * self.scale).max(0.02);
+            self.hand = (bob.distance(mouse) < reach).then_some(mouse);
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.hand = None;
+        }
+        cart.hand.target = None;
+        let Some(hand) = &mut self.hand else {
+            return;
+        };
+        *hand = mouse;
+        let target = [
+            self.to_world(mouse.x),
+            ((mouse.y - pivot) / self.scale) as f64,
+        ];
+        if !cart.paused {
+            cart.hand.target = Some(target);
+            return;
+        }
+        // the sideways reach of the pendulum, undoing the Furuta arm's swing and foreshortening
+        let reach = match cart.plant {
+            Plant::Furuta if x.cos().abs() > 0.1 => {
+                (target[0] - cart.params.l_arm * x.sin()) / x.cos()
+            }
+            Plant::Furuta => return,
+            _ => target[0] - x,
+        };
+        cart.place(reach.atan2(-target[1]));
+    }
+
+    fn draw_hand(&self, bob: Vec2, thickness: f32, color: Color) {
+        if let Some(hand) = self.hand {
+            draw_line(bob.x, bob.y, hand.x, hand.y, 0.5 * thickness, color);
+            draw_circle(hand.x, hand.y, 0.005, color);
+        }
+    }
+
+    pub fn draw_cart(
+        &self,
+        cart: &Cart,
+        back_color: Color,
+        color: Color,
+        thickness: f32,
+        length: f32,
+        depth: f32,
+    ) {
+        let state = cart.interpolated();
+        draw_line(-length, -depth, length, -depth, thickness, color);
+        if cart.plant == Plant::Furuta {
+            self.draw_furuta(cart, color, thickness, depth);
+            return;
+        }
+        let x = self.to_screen(state.x);
+        let R = cart.params.R as f32 * self.scale;
+        let (c, s) = (
+            (state.x / cart.params.R).cos() as f32,
+            (state.x / cart.params.R).sin() as f32,
+        );
+
+        let ticks = (9. / self.scale) as i32;
+        let gap = 2. / ticks as f32;
+        let offset = (self.camera.y as f32 * self.scale) % gap;
+        for i in 0..ticks + 2 {
+            draw_line(
+                (-offset + gap * i as f32 - 1.) * length,
+                -depth - 0.002,
+                (-offset + gap * i as f32 - 1.) * length - 0.1 * self.scale,
+                -depth - 0.1 * self.scale,
+                thickness,
+                color,
+            );
+        }
+        draw_rectangle(
+            -1.,
+            -depth - 0.001,
+            1. - length - 0.003,
+            -0.11 * self.scale,
+            back_color,
+        );
+        draw_rectangle(
+            length + 0.003,
+            -depth - 0.001,
+            1. - length - 0.003,
+            -0.11 * self.scale,
+            back_color,
+        );
+
+        let (w, h) = (R * 10., R * 3.5);
+        if cart.track.enable {
+            // the stops sit half a cart width beyond the travel of its centre
+            for side in [-1., 1.] {
+                let stop = self.to_screen(side * 0.5 * cart.track.length) + side as f32 * 0.5 * w;
+                draw_rectangle(stop, -depth, side as f32 * 0.5 * R, h + 2. * R, color);
+            }
+        }
+
+        if cart.position.enable {
+            let target = self.to_screen(cart.position.target);
+            let size = if self.dragging_target { 0.03 } else { 0.02 };
+            draw_triangle(
+                vec2(target, -depth - 0.005),
+                vec2(target - 0.5 * size, -depth - 0.005 - size),
+                vec2(target + 0.5 * size, -depth - 0.005 - size),
+                color,
+            );
+        }
+
+        // cart
+        draw_rectangle_lines(x - 0.5 * w, -depth + 2. * R, w, h, thickness * 2., color);
+
+        // wheels
+        draw_circle_lines(x - 0.30 * w, -depth + R, R, thickness, color);
+        draw_circle_lines(x + 0.30 * w, -depth + R, R, thickness, color);
+        draw_line(
+            x - 0.30 * w,
+            -depth + R,
+            x - 0.30 * w - R * c,
+            -depth + R + R * s,
+            thickness,
+            color,
+        );
+        draw_line(
+            x + 0.30 * w,
+            -depth + R,
+            x + 0.30 * w - R * c,
+            -depth + R + R * s,
+            thickness,
+            color,
+        );
+
+        let (c, s) = ((state.th).cos() as f32, (state.th).sin() as f32);
+        let l = cart.params.l as f32 * self.scale;
+        // pendulum
+        draw_line(
+            x,
+            -depth + h + 2. * R,
+            x + (l - R) * s,
+            -depth + h + 2. * R - (l - R) * c,
+            thickness,
+            color,
+        );
+        let bob = vec2(x + l * s, -depth + h + 2. * R - l * c);
+        draw_circle_lines(bob.x, bob.y, R, thickness, color);
+        self.draw_hand(bob, thickness, color);
+        if cart.plant == Plant::Double {
+            let (c, s) = ((state.th2).cos() as f32, (state.th2).sin() as f32);
+            let l = cart.params.l_2 as f32 * self.scale;
+            draw_line(
+                bob.x + R * s,
+                bob.y - R * c,
+                bob.x + (l - R) * s,
+                bob.y - (l - R) * c,
+                thickness,
+                color,
+            );
+            draw_circle_lines(bob.x + l * s, bob.y - l * c, R, thickness, color);
+        }
+        draw_circle(x, -depth + 2. * R + h, 0.01, color);
+    }
+
+    /// Front view of the rotary plant, the arm turning about the vertical shaft
+    /// and the pendulum swinging in the plane normal to it
+    fn draw_furuta(&self, cart: &Cart, color: Color, thickness: f32, depth: f32) {
+        let state = cart.interpolated();
+        let p = &cart.params;
+        let (l, r, R) = (
+            p.l as f32 * self.scale,
+            p.l_arm as f32 * self.scale,
+            p.R as f32 * self.scale,
+        );
+        let base = vec2(self.to_screen(0.), -depth);
+        let hub = base + vec2(0., l + 2. * R);
+        draw_rectangle_lines(base.x - 2. * R, base.y, 4. * R, R, thickness * 2., color);
+        draw_line(base.x, base.y + R, hub.x, hub.y, thickness * 2., color);
+
+        let (c, s) = ((state.x).cos() as f32, (state.x).sin() as f32);
+        let tip = hub + vec2(r * s, 0.);
+        draw_line(hub.x, hub.y, tip.x, tip.y, thickness * 2., color);
+        draw_circle(hub.x, hub.y, 0.01, color);
+
+        // the pendulum's sideways swing foreshortens as the arm turns towards the viewer
+        let (cth, sth) = ((state.th).cos() as f32, (state.th).sin() as f32);
+        let dir = vec2(sth * c, -cth);
+        let bob = tip + dir * l;
+        let end = tip + dir * (l - R);
+        draw_line(tip.x, tip.y, end.x, end.y, thickness, color);
+        draw_circle_lines(bob.x, bob.y, R, thickness, color);
+        self.draw_hand(bob, thickness, color);
+        draw_circle(tip.x, tip.y, 0.01, color);
+    }
+}